@@ -0,0 +1,102 @@
+use wasm_actions::prelude::Error;
+
+/// Reads a private key file, refusing files that other users can read.
+pub fn read_private_key(path: &str) -> Result<String, Error> {
+    let mode = platform::mode(path)
+        .map_err(|e| Error::from(format!("failed to stat private-key-path '{path}': {e}")))?;
+    if mode.is_some_and(world_readable) {
+        return Err(Error::from(format!(
+            "private-key-path '{path}' must not be world-readable, run `chmod o-r` on it"
+        )));
+    }
+
+    platform::read_to_string(path)
+        .map_err(|e| Error::from(format!("failed to read private-key-path '{path}': {e}")))
+}
+
+fn world_readable(mode: u32) -> bool {
+    mode & 0o004 != 0
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    use wasm_actions::prelude::Error;
+    use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+    #[wasm_bindgen(module = "node:fs")]
+    extern "C" {
+        type Stats;
+
+        #[wasm_bindgen(catch, js_name = statSync)]
+        fn stat_sync(path: &str) -> Result<Stats, JsValue>;
+
+        #[wasm_bindgen(method, getter)]
+        fn mode(this: &Stats) -> u32;
+
+        #[wasm_bindgen(catch, js_name = readFileSync)]
+        fn read_file_sync(path: &str, encoding: &str) -> Result<String, JsValue>;
+    }
+
+    #[wasm_bindgen(module = "node:os")]
+    extern "C" {
+        fn platform() -> String;
+    }
+
+    /// Returns `None` on Windows, where Node.js does not report meaningful permission bits.
+    pub fn mode(path: &str) -> Result<Option<u32>, Error> {
+        let stats = stat_sync(path).map_err(Error::from)?;
+        if platform() == "win32" {
+            Ok(None)
+        } else {
+            Ok(Some(stats.mode()))
+        }
+    }
+
+    pub fn read_to_string(path: &str) -> Result<String, Error> {
+        read_file_sync(path, "utf8").map_err(Error::from)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use wasm_actions::prelude::Error;
+
+    #[cfg(unix)]
+    pub fn mode(path: &str) -> Result<Option<u32>, Error> {
+        use std::os::unix::fs::PermissionsExt;
+        let metadata = std::fs::metadata(path).map_err(Error::new)?;
+        Ok(Some(metadata.permissions().mode()))
+    }
+
+    #[cfg(not(unix))]
+    pub fn mode(path: &str) -> Result<Option<u32>, Error> {
+        std::fs::metadata(path).map_err(Error::new)?;
+        Ok(None)
+    }
+
+    pub fn read_to_string(path: &str) -> Result<String, Error> {
+        std::fs::read_to_string(path).map_err(Error::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn detects_world_readable_modes() {
+        assert!(world_readable(0o100644));
+        assert!(world_readable(0o100604));
+        assert!(!world_readable(0o100600));
+        assert!(!world_readable(0o100640));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn reports_missing_private_key_file() {
+        assert!(read_private_key("does-not-exist.pem")
+            .unwrap_err()
+            .to_string()
+            .starts_with("failed to stat private-key-path 'does-not-exist.pem'"));
+    }
+}
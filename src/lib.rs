@@ -1,10 +1,23 @@
 use base64ct::{Base64UrlUnpadded, Encoding};
 use http::{uri::Authority, Uri};
-use log::{error, warn};
+use log::{error, info, warn};
+mod key_file;
 mod sign;
+mod signer_command;
+#[cfg(all(test, target_arch = "wasm32"))]
+mod stand_in;
+mod vault_transit;
 use serde::{Deserialize, Serialize};
-use sign::sign_sha256;
-use std::collections::BTreeMap;
+use sign::{
+    check_pem, check_pkcs8, fingerprint_sha256, load_pkey, normalize_pkey, sign_sha256,
+    split_pkeys, KeyFormat, KeyProblem, RsaKeyInfo,
+};
+use std::{
+    cell::{Cell, OnceCell},
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+use vault_transit::VaultTransit;
 use wasm_actions::{
     derive::{wasm_action, ActionInput, ActionOutput},
     prelude::{add_mask, derive::Action, env, Error},
@@ -17,61 +30,190 @@ use wasm_actions::{
 struct GhTokenGen;
 
 impl GhTokenGen {
-    fn create_payload(app_id: String) -> Result<Payload, Error> {
-        let now = unix_now();
+    /// `clock_offset` is added to the local clock to correct for a measured drift.
+    fn create_payload(
+        app_id: String,
+        lifetime: JwtLifetime,
+        clock_offset: i64,
+    ) -> Result<Payload, Error> {
+        let now = unix_now() + clock_offset;
 
         Ok(Payload {
             iss: app_id,
-            // 60 seconds in the past to allow for clock drift
+            // in the past to allow for clock drift
             // https://docs.github.com/en/apps/creating-github-apps/authenticating-with-a-github-app/generating-a-json-web-token-jwt-for-a-github-app#example-using-ruby-to-generate-a-jwt
-            iat: now - 60,
-            exp: now + 60,
+            iat: now - lifetime.clock_skew,
+            exp: now + lifetime.expiration,
         })
     }
 }
 
+impl GhTokenGen {
+    /// Loads every private key as a signer along with its fingerprint,
+    /// failing if a fingerprint is not listed in expected-key-fingerprint.
+    fn load_private_keys(input: &Input) -> Result<(Vec<JwtSigner>, Vec<String>), Error> {
+        let private_keys = input.private_keys()?;
+        let passphrase = input.private_key_passphrase();
+        let expected_fingerprints = input.expected_key_fingerprints();
+        let mut signers = Vec::with_capacity(private_keys.len());
+        let mut fingerprints = Vec::with_capacity(private_keys.len());
+        for private_key in private_keys {
+            let pkey = private_key.load(passphrase.as_deref())?;
+            let fingerprint = fingerprint_sha256(&pkey)?;
+            verify_key_fingerprint(&private_key.source, &fingerprint, &expected_fingerprints)?;
+            fingerprints.push(fingerprint);
+            signers.push(JwtSigner::PrivateKey(pkey));
+        }
+        Ok((signers, fingerprints))
+    }
+}
+
 impl Action<Input, Output> for GhTokenGen {
     async fn main(input: Input) -> Result<Output, Error> {
         let client_id = input.client_id()?;
-        let private_key = input.private_key()?;
+        // the key behind an external signer is unknown, so it has no fingerprint
+        let (signers, mut fingerprints) = if let Some(vault) = input.vault_transit()? {
+            (vec![JwtSigner::Vault(vault)], vec![String::new()])
+        } else if let Some(command) = input.signer_command()? {
+            (vec![JwtSigner::Command(command)], vec![String::new()])
+        } else {
+            Self::load_private_keys(&input)?
+        };
+        let credentials = AppCredentials {
+            client_id,
+            lifetime: input.jwt_lifetime()?,
+            signers,
+        };
+
+        if input.jwt_only {
+            // without an API call there is no way to tell which key GitHub accepts
+            if credentials.signers.len() > 1 {
+                warn!(
+                    "jwt-only signs with private key #1 of {} without checking it against GitHub",
+                    credentials.signers.len()
+                );
+            }
+            let authorization_header = credentials.authorization_headers(0).await?.swap_remove(0);
+            return Ok(Output {
+                token: String::new(),
+                tokens: String::new(),
+                token_batches: String::new(),
+                repository_tokens: String::new(),
+                installation_id: String::new(),
+                app_slug: String::new(),
+                target_type: String::new(),
+                account_login: String::new(),
+                key_fingerprint: fingerprints.swap_remove(0),
+                jwt: bare_jwt(&authorization_header).to_string(),
+                expires_at: String::new(),
+            });
+        }
+
         let endpoint = ApiEndpoint::from_inputs(&input.github_api_url, &input.endpoint)?;
-        let target = InstallationTarget::resolve(&input)?;
-        let authorization_header = JwtBuilder {
-            payload: Self::create_payload(client_id)?,
-            pkey: private_key,
+        let permissions = permissions_from_inputs();
+        let repository_query = input.repository_query()?;
+        let owner_type = input.owner_type()?;
+        let mut access_tokens: Vec<AccessToken> = Vec::new();
+        for target in InstallationTarget::resolve_all(&input)? {
+            let result = AccessTokenBuilder {
+                endpoint: endpoint.clone(),
+                target,
+                repository_query: repository_query.clone(),
+                owner_type,
+                permissions: permissions.clone(),
+                credentials: credentials.clone(),
+                server_clock_drift: Cell::new(None),
+                jwt_time_rejected: Cell::new(false),
+                selected_repositories: OnceCell::new(),
+                client: reqwest::Client::new(),
+            }
+            .build()
+            .await;
+            match result {
+                Ok(batch) => {
+                    for access_token in &batch {
+                        add_mask(&access_token.token);
+                    }
+                    access_tokens.extend(batch);
+                }
+                Err(e) => {
+                    revoke_tokens(&endpoint, access_tokens).await;
+                    return Err(e);
+                }
+            }
         }
-        .build_authorization_header()
-        .await?;
-        add_mask(&authorization_header);
-
-        let access_token = AccessTokenBuilder {
-            endpoint,
-            target,
-            permissions: permissions_from_inputs(),
-            authorization_header,
-            client: reqwest::Client::new(),
+
+        let mut tokens = BTreeMap::new();
+        for access_token in &access_tokens {
+            // an account split into batches is keyed by its first token
+            tokens
+                .entry(access_token.account.clone())
+                .or_insert_with(|| access_token.token.clone());
         }
-        .build()
-        .await?;
-        add_mask(&access_token.token);
+        let token_batches = access_tokens
+            .iter()
+            .map(|access_token| TokenBatch {
+                account: access_token.account.clone(),
+                token: access_token.token.clone(),
+                repositories: access_token.repositories.clone(),
+                expires_at: access_token.expires_at.clone(),
+            })
+            .collect::<Vec<_>>();
+        let repository_tokens = access_tokens
+            .iter()
+            .flat_map(|access_token| {
+                access_token
+                    .repositories
+                    .iter()
+                    .map(|repository| (repository.clone(), access_token.token.clone()))
+            })
+            .collect::<BTreeMap<_, _>>();
+        let access_token = access_tokens.swap_remove(0);
         Ok(Output {
+            tokens: serde_json::to_string(&tokens).map_err(Error::new)?,
+            token_batches: serde_json::to_string(&token_batches).map_err(Error::new)?,
+            repository_tokens: serde_json::to_string(&repository_tokens).map_err(Error::new)?,
             token: access_token.token,
             installation_id: access_token.installation_id.to_string(),
             app_slug: access_token.app_slug,
+            target_type: access_token.target_type,
+            account_login: access_token.account_login,
+            key_fingerprint: fingerprints.swap_remove(access_token.key_index),
+            jwt: if input.output_jwt {
+                bare_jwt(&access_token.authorization_header).to_string()
+            } else {
+                String::new()
+            },
             expires_at: access_token.expires_at,
         })
     }
 
     async fn post(input: Input, state: Output) -> Result<(), Error> {
-        RemoveAccessTokenRequest {
-            endpoint: ApiEndpoint::from_inputs(&input.github_api_url, &input.endpoint)?,
-            token: state.token,
-            expires_at: state.expires_at,
-            skip_token_revoke: input.skip_token_revoke,
-            client: reqwest::Client::new(),
+        let endpoint = ApiEndpoint::from_inputs(&input.github_api_url, &input.endpoint)?;
+        let issued_tokens = state.issued_tokens();
+        let mut failures = 0;
+        for (token, expires_at) in issued_tokens.iter().cloned() {
+            let revoked = RemoveAccessTokenRequest {
+                endpoint: endpoint.clone(),
+                token,
+                expires_at,
+                skip_token_revoke: input.skip_token_revoke,
+                client: reqwest::Client::new(),
+            }
+            .execute()
+            .await;
+            if let Err(e) = revoked {
+                warn!("token revocation failed: {e}");
+                failures += 1;
+            }
         }
-        .execute()
-        .await
+        if failures > 0 {
+            return Err(Error::from(format!(
+                "{failures} of {} tokens could not be revoked",
+                issued_tokens.len()
+            )));
+        }
+        Ok(())
     }
 }
 
@@ -87,10 +229,82 @@ struct Input {
     client_id: String,
     #[input(
         name = "private-key",
-        required = true,
+        default = "",
         description = "The application's PEM-encoded private key"
     )]
     private_key: String,
+    #[input(
+        name = "private-key-path",
+        default = "",
+        description = "Path to a file containing the application's PEM-encoded private key"
+    )]
+    private_key_path: String,
+    #[input(
+        name = "private-key-passphrase",
+        default = "",
+        description = "Passphrase to decrypt an encrypted PKCS#8 private key"
+    )]
+    private_key_passphrase: String,
+    #[input(
+        name = "signer-command",
+        default = "",
+        description = "Shell command that reads the JWT signing input on stdin and prints its RS256 signature, instead of using private-key"
+    )]
+    signer_command: String,
+    #[input(
+        name = "vault-address",
+        default = "",
+        description = "HashiCorp Vault address; if set, a Vault Transit key signs the JWT instead of private-key"
+    )]
+    vault_address: String,
+    #[input(
+        name = "vault-token",
+        default = "",
+        description = "Vault token allowed to use the Transit sign endpoint"
+    )]
+    vault_token: String,
+    #[input(
+        name = "vault-transit-mount",
+        default = "transit",
+        description = "Mount path of the Vault Transit secrets engine"
+    )]
+    vault_transit_mount: String,
+    #[input(
+        name = "vault-transit-key",
+        default = "",
+        description = "Name of the Vault Transit RSA key that signs the JWT"
+    )]
+    vault_transit_key: String,
+    #[input(
+        name = "expected-key-fingerprint",
+        default = "",
+        description = "Comma or newline-separated list of allowed private key SHA-256 fingerprints"
+    )]
+    expected_key_fingerprint: String,
+    #[input(
+        name = "jwt-expiration-seconds",
+        default = "60",
+        description = "Seconds from now until the App JWT expires; at most 600 together with jwt-clock-skew-seconds"
+    )]
+    jwt_expiration_seconds: String,
+    #[input(
+        name = "jwt-clock-skew-seconds",
+        default = "60",
+        description = "Seconds to backdate the App JWT issue time by to allow for clock drift; at most 600 together with jwt-expiration-seconds"
+    )]
+    jwt_clock_skew_seconds: String,
+    #[input(
+        name = "output-jwt",
+        default = "false",
+        description = "If true, the App JWT is exposed as the jwt output"
+    )]
+    output_jwt: bool,
+    #[input(
+        name = "jwt-only",
+        default = "false",
+        description = "If true, only the App JWT is generated and no installation token is created"
+    )]
+    jwt_only: bool,
     #[input(
         name = "github-api-url",
         default = "https://api.github.com",
@@ -106,21 +320,75 @@ struct Input {
     #[input(
         name = "owner",
         default = "",
-        description = "The owner of the GitHub App installation"
+        description = "Comma or newline-separated list of owners of GitHub App installations"
     )]
     owner: String,
+    #[input(
+        name = "owner-type",
+        default = "",
+        description = "organization or user to look the installation of owner up at that account type only, or auto to read the type from GitHub first; by default both are tried. Only auto can be used with multiple owners"
+    )]
+    owner_type: String,
     #[input(
         name = "repositories",
         default = "",
-        description = "Comma or newline-separated list of repositories to grant access to"
+        description = "Comma or newline-separated list of repositories to grant access to; * and ? match any characters"
     )]
     repositories: String,
+    #[input(
+        name = "repository-ids",
+        default = "",
+        description = "Comma or newline-separated list of IDs of repositories to grant access to"
+    )]
+    repository_ids: String,
+    #[input(
+        name = "repository-topics",
+        default = "",
+        description = "Comma or newline-separated list of topics; repositories of the installation with these topics are granted access too"
+    )]
+    repository_topics: String,
+    #[input(
+        name = "repository-topics-match",
+        default = "all",
+        description = "Whether repositories need all or any of repository-topics"
+    )]
+    repository_topics_match: String,
+    #[input(
+        name = "repository-properties",
+        default = "",
+        description = "Comma or newline-separated list of name=value custom properties; organization repositories with all of them are granted access too"
+    )]
+    repository_properties: String,
     #[input(
         name = "enterprise",
         default = "",
         description = "The slug of the enterprise account where the GitHub App is installed"
     )]
     enterprise: String,
+    #[input(
+        name = "installation-id",
+        default = "",
+        description = "GitHub App installation ID; if set, the installation is not looked up, so app-slug, target-type, and account-login stay empty and only the first private key is used unless verify-installation is true"
+    )]
+    installation_id: String,
+    #[input(
+        name = "verify-installation",
+        default = "false",
+        description = "If true, the installation given by installation-id is fetched to fill in app-slug and to check that it is not suspended"
+    )]
+    verify_installation: bool,
+    #[input(
+        name = "installation",
+        default = "",
+        description = "If auto, the installation is discovered from the installations of the GitHub App, filtered by owner and installation-account-type"
+    )]
+    installation: String,
+    #[input(
+        name = "installation-account-type",
+        default = "",
+        description = "Organization, User, or Enterprise; the account type of the installation discovered by installation: auto"
+    )]
+    installation_account_type: String,
     #[input(
         name = "skip-token-revoke",
         default = "false",
@@ -131,9 +399,45 @@ struct Input {
     repo: String,
     #[input(env = "GITHUB_REPOSITORY_OWNER")]
     repo_owner: String,
+    #[input(env = "GITHUB_REPOSITORY_ID")]
+    repo_id: String,
 }
 
 impl Input {
+    /// Whether the installation is discovered with `installation: auto`.
+    fn discovers_installation(&self) -> Result<bool, Error> {
+        let installation = self.installation.trim();
+        if installation.eq_ignore_ascii_case("auto") {
+            Ok(true)
+        } else if installation.is_empty() {
+            if !self.installation_account_type.trim().is_empty() {
+                return Err(Error::from(
+                    "installation-account-type can only be used with installation: auto",
+                ));
+            }
+            Ok(false)
+        } else {
+            Err(Error::from(format!(
+                "installation must be 'auto' or empty, got '{installation}'"
+            )))
+        }
+    }
+
+    fn installation_account_type(&self) -> Result<Option<String>, Error> {
+        match self.installation_account_type.trim() {
+            "" => Ok(None),
+            account_type => ["Organization", "User", "Enterprise"]
+                .into_iter()
+                .find(|known| known.eq_ignore_ascii_case(account_type))
+                .map(|known| Some(known.to_string()))
+                .ok_or_else(|| {
+                    Error::from(format!(
+                        "installation-account-type must be Organization, User, or Enterprise, got '{account_type}'"
+                    ))
+                }),
+        }
+    }
+
     fn client_id(&self) -> Result<String, Error> {
         let client_id = if self.client_id.trim().is_empty() {
             self.app_id.trim()
@@ -150,11 +454,208 @@ impl Input {
         }
     }
 
-    fn private_key(&self) -> Result<String, Error> {
-        if self.private_key.trim().is_empty() {
-            Err(Error::from("private-key must be set to a non-empty string"))
+    /// Returns every key found in the private key input, in the order they should be tried.
+    fn private_keys(&self) -> Result<Vec<PrivateKey>, Error> {
+        let path = self.private_key_path.trim();
+        let (data, source) = match (self.private_key.trim().is_empty(), path.is_empty()) {
+            (false, false) => {
+                return Err(Error::from(
+                    "private-key and private-key-path cannot be used together",
+                ))
+            }
+            (true, true) => {
+                return Err(Error::from(
+                    "private-key, private-key-path, signer-command, or vault-address must be set to a non-empty string",
+                ))
+            }
+            (false, true) => (
+                self.private_key.replace("\\n", "\n"),
+                "private-key".to_string(),
+            ),
+            (true, false) => (
+                key_file::read_private_key(path)?,
+                format!("private-key-path '{path}'"),
+            ),
+        };
+
+        let blocks = split_pkeys(&data);
+        if blocks.len() == 1 {
+            return Ok(vec![PrivateKey::new(blocks[0], source)]);
+        }
+        Ok(blocks
+            .into_iter()
+            .enumerate()
+            .map(|(index, block)| PrivateKey::new(block, format!("{source} key #{}", index + 1)))
+            .collect())
+    }
+
+    fn signer_command(&self) -> Result<Option<String>, Error> {
+        let command = self.signer_command.trim();
+        if command.is_empty() {
+            return Ok(None);
+        }
+        if !self.private_key.trim().is_empty() || !self.private_key_path.trim().is_empty() {
+            return Err(Error::from(
+                "signer-command cannot be used with private-key or private-key-path",
+            ));
+        }
+        if !self.expected_key_fingerprint.trim().is_empty() {
+            return Err(Error::from(
+                "expected-key-fingerprint cannot be used with signer-command",
+            ));
+        }
+        Ok(Some(command.to_string()))
+    }
+
+    fn vault_transit(&self) -> Result<Option<VaultTransit>, Error> {
+        let address = self.vault_address.trim();
+        if address.is_empty() {
+            return Ok(None);
+        }
+        if !self.private_key.trim().is_empty()
+            || !self.private_key_path.trim().is_empty()
+            || !self.signer_command.trim().is_empty()
+        {
+            return Err(Error::from(
+                "vault-address cannot be used with private-key, private-key-path, or signer-command",
+            ));
+        }
+        if !self.expected_key_fingerprint.trim().is_empty() {
+            return Err(Error::from(
+                "expected-key-fingerprint cannot be used with vault-address",
+            ));
+        }
+
+        let key = self.vault_transit_key.trim();
+        let token = self.vault_token.trim();
+        if key.is_empty() || token.is_empty() {
+            return Err(Error::from(
+                "vault-transit-key and vault-token must be set to use vault-address",
+            ));
+        }
+        add_mask(token);
+        Ok(Some(VaultTransit::new(
+            address,
+            self.vault_transit_mount.trim(),
+            key,
+            token,
+        )))
+    }
+
+    /// The accounts listed in `owner`, each once; a duplicate would mint a second token under the same key.
+    fn owners(&self) -> Vec<String> {
+        let mut owners = parse_list(&self.owner);
+        let mut seen = BTreeSet::new();
+        owners.retain(|owner| seen.insert(owner.to_ascii_lowercase()));
+        owners
+    }
+
+    /// The owner and name of the repository the workflow runs in.
+    fn current_repository(&self) -> Result<(&str, &str), Error> {
+        self.repo
+            .split_once('/')
+            .ok_or_else(|| Error::from("GITHUB_REPOSITORY must be '<owner>/<repo>'"))
+    }
+
+    /// Unlike the name, the ID survives the repository being renamed or transferred mid-run.
+    fn current_repository_id(&self) -> Option<u64> {
+        self.repo_id.trim().parse().ok()
+    }
+
+    fn repository_ids(&self) -> Result<Vec<u64>, Error> {
+        parse_list(&self.repository_ids)
+            .into_iter()
+            .map(|id| {
+                id.parse::<u64>()
+                    .map_err(|_| Error::from(format!("invalid repository ID '{id}'")))
+            })
+            .collect()
+    }
+
+    fn owner_type(&self) -> Result<Option<OwnerType>, Error> {
+        match self.owner_type.trim().to_ascii_lowercase().as_str() {
+            "" => Ok(None),
+            "organization" => Ok(Some(OwnerType::Organization)),
+            "user" => Ok(Some(OwnerType::User)),
+            "auto" => Ok(Some(OwnerType::Auto)),
+            value => Err(Error::from(format!(
+                "owner-type must be 'organization', 'user', or 'auto', got '{value}'"
+            ))),
+        }
+    }
+
+    fn repository_query(&self) -> Result<Option<RepositoryQuery>, Error> {
+        let topics = parse_list(&self.repository_topics)
+            .into_iter()
+            .map(|topic| topic.to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        let properties = parse_list(&self.repository_properties)
+            .into_iter()
+            .map(|property| match property.split_once('=') {
+                Some((name, value)) if !name.trim().is_empty() => {
+                    Ok((name.trim().to_string(), value.trim().to_string()))
+                }
+                _ => Err(Error::from(format!(
+                    "invalid repository property '{property}', expected 'name=value'"
+                ))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if topics.is_empty() && properties.is_empty() {
+            return Ok(None);
+        }
+        let match_all = match self.repository_topics_match.trim() {
+            "all" => true,
+            "any" => false,
+            value => {
+                return Err(Error::from(format!(
+                    "repository-topics-match must be 'all' or 'any', got '{value}'"
+                )))
+            }
+        };
+        Ok(Some(RepositoryQuery {
+            topics,
+            match_all,
+            properties,
+        }))
+    }
+
+    fn expected_key_fingerprints(&self) -> Vec<String> {
+        self.expected_key_fingerprint
+            .split([',', '\n'])
+            .map(str::trim)
+            .filter(|fingerprint| !fingerprint.is_empty())
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    fn jwt_lifetime(&self) -> Result<JwtLifetime, Error> {
+        let lifetime = JwtLifetime {
+            expiration: parse_seconds(
+                "jwt-expiration-seconds",
+                &self.jwt_expiration_seconds,
+                1..=JwtLifetime::MAX_SECONDS,
+            )?,
+            clock_skew: parse_seconds(
+                "jwt-clock-skew-seconds",
+                &self.jwt_clock_skew_seconds,
+                0..=JwtLifetime::MAX_SECONDS,
+            )?,
+        };
+        let window = lifetime.expiration + lifetime.clock_skew;
+        if window > JwtLifetime::MAX_SECONDS {
+            return Err(Error::from(format!(
+                "jwt-expiration-seconds plus jwt-clock-skew-seconds must be at most {}, got {window}",
+                JwtLifetime::MAX_SECONDS
+            )));
+        }
+        Ok(lifetime)
+    }
+
+    fn private_key_passphrase(&self) -> Option<String> {
+        if self.private_key_passphrase.is_empty() {
+            None
         } else {
-            Ok(self.private_key.replace("\\n", "\n"))
+            Some(self.private_key_passphrase.clone())
         }
     }
 }
@@ -215,10 +716,78 @@ impl ApiEndpoint {
 struct Output {
     #[output(name = "token", description = "Generated token")]
     token: String,
+    #[output(
+        name = "tokens",
+        description = "JSON object mapping each owner to its generated token"
+    )]
+    tokens: String,
+    #[output(
+        name = "token-batches",
+        description = "JSON array of every generated token with its owner, the owner/repository names it covers, and its expiration; selections beyond 500 repositories are split into several tokens"
+    )]
+    token_batches: String,
+    #[output(
+        name = "repository-tokens",
+        description = "JSON object mapping each owner/repository covered by a token to that token"
+    )]
+    repository_tokens: String,
     #[output(name = "installation-id", description = "GitHub App installation ID")]
     installation_id: String,
     #[output(name = "app-slug", description = "GitHub App slug")]
     app_slug: String,
+    #[output(
+        name = "target-type",
+        description = "Type of the account the GitHub App is installed on: Organization, User, or Enterprise; empty with installation-id unless verify-installation is true, and with jwt-only"
+    )]
+    target_type: String,
+    #[output(
+        name = "account-login",
+        description = "Login of the account the GitHub App is installed on; empty with installation-id unless verify-installation is true, and with jwt-only"
+    )]
+    account_login: String,
+    #[output(
+        name = "key-fingerprint",
+        description = "SHA-256 fingerprint of the private key used to sign the JWT"
+    )]
+    key_fingerprint: String,
+    #[output(
+        name = "jwt",
+        description = "App JWT, set when output-jwt or jwt-only is true"
+    )]
+    jwt: String,
+    expires_at: String,
+}
+
+impl Output {
+    /// Returns every token minted by the main step with its expiration.
+    fn issued_tokens(&self) -> Vec<(String, String)> {
+        if let Ok(batches) = serde_json::from_str::<Vec<TokenBatch>>(&self.token_batches) {
+            return batches
+                .into_iter()
+                .map(|batch| match batch.expires_at.is_empty() {
+                    true => (batch.token, self.expires_at.clone()),
+                    false => (batch.token, batch.expires_at),
+                })
+                .collect();
+        }
+        // state saved before token-batches existed
+        serde_json::from_str::<BTreeMap<String, String>>(&self.tokens)
+            .map(|tokens| tokens.into_values().collect())
+            .unwrap_or_else(|_| vec![self.token.clone()])
+            .into_iter()
+            .map(|token| (token, self.expires_at.clone()))
+            .collect()
+    }
+}
+
+/// An entry of the `token-batches` output.
+#[derive(Serialize, Deserialize)]
+struct TokenBatch {
+    account: String,
+    token: String,
+    repositories: Vec<String>,
+    // state saved before expires_at was added falls back to the `expires-at` output
+    #[serde(default)]
     expires_at: String,
 }
 
@@ -229,9 +798,154 @@ struct Payload {
     exp: i64,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct JwtLifetime {
+    expiration: i64,
+    clock_skew: i64,
+}
+
+impl JwtLifetime {
+    /// GitHub rejects App JWTs that expire more than 10 minutes after they were issued.
+    const MAX_SECONDS: i64 = 600;
+}
+
+fn parse_seconds(
+    name: &str,
+    value: &str,
+    range: std::ops::RangeInclusive<i64>,
+) -> Result<i64, Error> {
+    value
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .filter(|seconds| range.contains(seconds))
+        .ok_or_else(|| {
+            Error::from(format!(
+                "{name} must be an integer between {} and {}",
+                range.start(),
+                range.end()
+            ))
+        })
+}
+
+struct PrivateKey {
+    pem: String,
+    /// Describes where the key came from, for error messages that must not include the key itself.
+    source: String,
+    format: KeyFormat,
+}
+
+impl PrivateKey {
+    fn new(data: &str, source: String) -> Self {
+        let (pem, format) = normalize_pkey(data);
+        Self {
+            pem,
+            source,
+            format,
+        }
+    }
+
+    /// Returns the key as PKCS#8 DER, after checking that GitHub can verify its signatures.
+    fn load(&self, passphrase: Option<&str>) -> Result<Vec<u8>, Error> {
+        check_pem(&self.pem).map_err(|problem| self.reject(problem))?;
+        let pkey = load_pkey(&self.pem, passphrase).map_err(|e| {
+            Error::from(format!(
+                "failed to load {} (detected {}): {e}",
+                self.source, self.format
+            ))
+        })?;
+        let key_info = check_pkcs8(&pkey).map_err(|problem| self.reject(problem))?;
+        if key_info.exponent == RsaKeyInfo::GITHUB_EXPONENT {
+            info!("{}: {key_info}", self.source);
+        } else {
+            warn!(
+                "{}: {key_info}, GitHub generates keys with exponent {}",
+                self.source,
+                RsaKeyInfo::GITHUB_EXPONENT
+            );
+        }
+        Ok(pkey)
+    }
+
+    fn reject(&self, problem: KeyProblem) -> Error {
+        error!("{} {problem}: {}", self.source, problem.hint());
+        Error::from(format!("{} {problem}", self.source))
+    }
+}
+
+/// Fails unless `fingerprint` is one of `expected`; an empty `expected` allows any key.
+fn verify_key_fingerprint(
+    source: &str,
+    fingerprint: &str,
+    expected: &[String],
+) -> Result<(), Error> {
+    let normalize = |fingerprint: &str| fingerprint.trim_start_matches("SHA256:").to_string();
+    if expected.is_empty()
+        || expected
+            .iter()
+            .any(|expected| normalize(expected) == normalize(fingerprint))
+    {
+        Ok(())
+    } else {
+        Err(Error::from(format!(
+            "{source} has fingerprint {fingerprint}, which does not match expected-key-fingerprint"
+        )))
+    }
+}
+
+#[derive(Clone)]
+enum JwtSigner {
+    /// PKCS#8 DER private key.
+    PrivateKey(Vec<u8>),
+    /// Shell command that signs its stdin.
+    Command(String),
+    /// Vault Transit key.
+    Vault(VaultTransit),
+}
+
+impl JwtSigner {
+    async fn sign_sha256(&self, buf: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::PrivateKey(pkey) => sign_sha256(buf, pkey).await,
+            Self::Command(command) => signer_command::sign_sha256(command, buf),
+            Self::Vault(vault) => vault.sign_sha256(buf).await,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct AppCredentials {
+    client_id: String,
+    lifetime: JwtLifetime,
+    /// In the order they should be tried.
+    signers: Vec<JwtSigner>,
+}
+
+impl AppCredentials {
+    /// Builds one JWT authorization header per signer.
+    async fn authorization_headers(&self, clock_offset: i64) -> Result<Vec<String>, Error> {
+        let mut authorization_headers = Vec::with_capacity(self.signers.len());
+        for signer in &self.signers {
+            let authorization_header = JwtBuilder {
+                payload: GhTokenGen::create_payload(
+                    self.client_id.clone(),
+                    self.lifetime,
+                    clock_offset,
+                )?,
+                signer: signer.clone(),
+            }
+            .build_authorization_header()
+            .await?;
+            add_mask(bare_jwt(&authorization_header));
+            authorization_headers.push(authorization_header);
+        }
+        Ok(authorization_headers)
+    }
+}
+
 struct JwtBuilder {
     payload: Payload,
-    pkey: String,
+    signer: JwtSigner,
 }
 
 impl JwtBuilder {
@@ -241,7 +955,7 @@ impl JwtBuilder {
         let payload = serde_json::to_string(&self.payload).map_err(Error::new)?;
         let payload = encode_base64_url(payload.as_bytes());
         let sig = format!("{header}.{payload}");
-        let sig = sign_sha256(sig.as_bytes(), &self.pkey).await?;
+        let sig = self.signer.sign_sha256(sig.as_bytes()).await?;
         let sig = encode_base64_url(&sig);
         Ok(format!("Bearer {header}.{payload}.{sig}"))
     }
@@ -250,8 +964,18 @@ impl JwtBuilder {
 struct AccessTokenBuilder {
     endpoint: ApiEndpoint,
     target: InstallationTarget,
+    /// Selects more repositories for a repository-scoped target.
+    repository_query: Option<RepositoryQuery>,
+    /// Narrows the installation lookup by owner down to one account type.
+    owner_type: Option<OwnerType>,
     permissions: Option<BTreeMap<String, String>>,
-    authorization_header: String,
+    credentials: AppCredentials,
+    /// Seconds the GitHub clock is ahead of ours, measured from the first response's `Date` header.
+    server_clock_drift: Cell<Option<i64>>,
+    /// Set when GitHub rejected the JWT because of its `iat` or `exp` claim.
+    jwt_time_rejected: Cell<bool>,
+    /// The repositories the first attempt listed and validated, reused by the retries.
+    selected_repositories: OnceCell<Option<Vec<String>>>,
     client: reqwest::Client,
 }
 
@@ -266,53 +990,256 @@ enum InstallationTarget {
     Repository {
         owner: String,
         repositories: Vec<String>,
+        repository_ids: Vec<u64>,
+        /// A repository to look the installation up by when `repositories` names none.
+        lookup: Option<String>,
+    },
+    Installation {
+        id: u64,
+        /// Whether to fetch the installation before creating the token.
+        lookup: bool,
+        repositories: Option<Vec<String>>,
+        repository_ids: Vec<u64>,
+    },
+    /// The single installation of the App, or the one matching the filters.
+    Auto {
+        account: Option<String>,
+        account_type: Option<String>,
+        repositories: Option<Vec<String>>,
+        repository_ids: Vec<u64>,
     },
 }
 
 impl InstallationTarget {
-    fn resolve(input: &Input) -> Result<Self, Error> {
-        let enterprise = input.enterprise.trim();
-        let owner = input.owner.trim();
-        let repositories = parse_repositories(&input.repositories);
-
-        if !enterprise.is_empty() {
-            if !owner.is_empty() || !repositories.is_empty() {
-                return Err(Error::from(
-                    "enterprise cannot be used with owner or repositories",
-                ));
+    /// Resolves one target per owner when `owner` lists several,
+    /// or when `repositories` names repositories of other owners.
+    fn resolve_all(input: &Input) -> Result<Vec<Self>, Error> {
+        if input.discovers_installation()? {
+            return Ok(vec![Self::resolve(input)?]);
+        }
+        let owners = input.owners();
+        if owners.len() <= 1 {
+            let owner = owners
+                .first()
+                .map_or(input.repo_owner.trim(), String::as_str);
+            let groups = group_repositories(owner, parse_list(&input.repositories))?;
+            if groups
+                .iter()
+                .all(|(group, _)| group.eq_ignore_ascii_case(owner))
+            {
+                return Ok(vec![Self::resolve(input)?]);
             }
-            return Ok(Self::Enterprise {
-                enterprise: enterprise.to_string(),
-            });
+            return Self::resolve_groups(input, owner, groups);
         }
-
-        if owner.is_empty() && repositories.is_empty() {
-            let (owner, repo) = input
-                .repo
-                .split_once('/')
-                .ok_or_else(|| Error::from("GITHUB_REPOSITORY must be '<owner>/<repo>'"))?;
-            return Ok(Self::Repository {
-                owner: owner.to_string(),
-                repositories: vec![repo.to_string()],
-            });
+        if !input.enterprise.trim().is_empty()
+            || !input.installation_id.trim().is_empty()
+            || !input.repositories.trim().is_empty()
+            || !input.repository_ids.trim().is_empty()
+        {
+            return Err(Error::from(
+                "multiple owners cannot be used with enterprise, installation-id, repositories, or repository-ids",
+            ));
         }
-
-        if !owner.is_empty() && repositories.is_empty() {
-            return Ok(Self::Owner {
-                owner: owner.to_string(),
-            });
+        if matches!(
+            input.owner_type()?,
+            Some(OwnerType::Organization | OwnerType::User)
+        ) {
+            return Err(Error::from(
+                "multiple owners cannot be used with owner-type organization or user, which would apply to every owner; use owner-type auto",
+            ));
         }
+        let selects_repositories = input.repository_query()?.is_some();
+        Ok(owners
+            .into_iter()
+            .map(|owner| {
+                if selects_repositories {
+                    Self::Repository {
+                        owner,
+                        repositories: Vec::new(),
+                        repository_ids: Vec::new(),
+                        lookup: None,
+                    }
+                } else {
+                    Self::Owner { owner }
+                }
+            })
+            .collect())
+    }
 
-        let owner = if owner.is_empty() {
-            input.repo_owner.trim()
-        } else {
-            owner
-        };
-        if owner.is_empty() {
+    fn resolve_groups(
+        input: &Input,
+        owner: &str,
+        mut groups: Vec<(String, Vec<String>)>,
+    ) -> Result<Vec<Self>, Error> {
+        if !input.enterprise.trim().is_empty() || !input.installation_id.trim().is_empty() {
+            return Err(Error::from(
+                "repositories of multiple owners cannot be used with enterprise or installation-id",
+            ));
+        }
+        if groups.iter().any(|(group, _)| group.is_empty()) {
             return Err(Error::from("owner could not be resolved"));
         }
-
-        let repositories = repositories
+        // IDs do not say which installation they belong to, so they go with `owner`
+        let repository_ids = input.repository_ids()?;
+        if !repository_ids.is_empty()
+            && !groups
+                .iter()
+                .any(|(group, _)| group.eq_ignore_ascii_case(owner))
+        {
+            groups.insert(0, (owner.to_string(), Vec::new()));
+        }
+        Ok(groups
+            .into_iter()
+            .map(|(group, repositories)| Self::Repository {
+                repository_ids: if group.eq_ignore_ascii_case(owner) {
+                    repository_ids.clone()
+                } else {
+                    Vec::new()
+                },
+                owner: group,
+                repositories,
+                lookup: None,
+            })
+            .collect())
+    }
+
+    fn resolve(input: &Input) -> Result<Self, Error> {
+        let enterprise = input.enterprise.trim();
+        let owners = input.owners();
+        let owner = owners.first().map_or("", String::as_str);
+        let repositories = parse_list(&input.repositories);
+        let mut repository_ids = input.repository_ids()?;
+        let selects_repositories =
+            !repository_ids.is_empty() || input.repository_query()?.is_some();
+
+        let installation_id = input.installation_id.trim();
+        if input.discovers_installation()? {
+            if !installation_id.is_empty() || !enterprise.is_empty() {
+                return Err(Error::from(
+                    "installation: auto cannot be used with installation-id or enterprise",
+                ));
+            }
+            if owners.len() > 1 {
+                return Err(Error::from(
+                    "installation: auto cannot be used with multiple owners",
+                ));
+            }
+            let account = owners.first().cloned();
+            let repositories = if repositories.is_empty() && !selects_repositories {
+                None
+            } else {
+                Some(
+                    repositories
+                        .into_iter()
+                        .map(|repository| match &account {
+                            Some(account) => parse_repository(account, &repository),
+                            None if repository.contains('/') => Err(Error::from(format!(
+                                "repository '{repository}' names its owner, which installation: auto needs as owner"
+                            ))),
+                            None => Ok(repository),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
+                )
+            };
+            return Ok(Self::Auto {
+                account,
+                account_type: input.installation_account_type()?,
+                repositories,
+                repository_ids,
+            });
+        }
+        if !installation_id.is_empty() {
+            if !enterprise.is_empty() {
+                return Err(Error::from(
+                    "installation-id cannot be used with enterprise",
+                ));
+            }
+            let id = installation_id
+                .parse::<u64>()
+                .ok()
+                .filter(|id| *id > 0)
+                .ok_or_else(|| Error::from("installation-id must be a positive integer"))?;
+            let repositories = if repositories.is_empty() && !selects_repositories {
+                if owner.is_empty() {
+                    // as without installation-id, only owner opts in to every repository
+                    let (_, repo) = input.current_repository()?;
+                    match input.current_repository_id() {
+                        Some(id) => {
+                            repository_ids = vec![id];
+                            Some(Vec::new())
+                        }
+                        None => Some(vec![repo.to_string()]),
+                    }
+                } else {
+                    None
+                }
+            } else {
+                let owner = if owner.is_empty() {
+                    input.repo_owner.trim()
+                } else {
+                    owner
+                };
+                Some(
+                    repositories
+                        .into_iter()
+                        .map(|repository| parse_repository(owner, &repository))
+                        .collect::<Result<Vec<_>, _>>()?,
+                )
+            };
+            return Ok(Self::Installation {
+                id,
+                lookup: input.verify_installation,
+                repositories,
+                repository_ids,
+            });
+        }
+
+        if !enterprise.is_empty() {
+            if !owner.is_empty() || !repositories.is_empty() || selects_repositories {
+                return Err(Error::from(
+                    "enterprise cannot be used with owner, repositories, repository-ids, repository-topics, or repository-properties",
+                ));
+            }
+            return Ok(Self::Enterprise {
+                enterprise: enterprise.to_string(),
+            });
+        }
+
+        if owner.is_empty() && repositories.is_empty() && !selects_repositories {
+            let (owner, repo) = input.current_repository()?;
+            return Ok(match input.current_repository_id() {
+                // the token names only the ID, but the lookup still goes through the repository
+                Some(id) => Self::Repository {
+                    owner: owner.to_string(),
+                    repositories: Vec::new(),
+                    repository_ids: vec![id],
+                    lookup: Some(repo.to_string()),
+                },
+                None => Self::Repository {
+                    owner: owner.to_string(),
+                    repositories: vec![repo.to_string()],
+                    repository_ids: Vec::new(),
+                    lookup: None,
+                },
+            });
+        }
+
+        if !owner.is_empty() && repositories.is_empty() && !selects_repositories {
+            return Ok(Self::Owner {
+                owner: owner.to_string(),
+            });
+        }
+
+        let owner = if owner.is_empty() {
+            input.repo_owner.trim()
+        } else {
+            owner
+        };
+        if owner.is_empty() {
+            return Err(Error::from("owner could not be resolved"));
+        }
+
+        let repositories = repositories
             .into_iter()
             .map(|repository| parse_repository(owner, &repository))
             .collect::<Result<Vec<_>, _>>()?;
@@ -320,42 +1247,318 @@ impl InstallationTarget {
         Ok(Self::Repository {
             owner: owner.to_string(),
             repositories,
+            repository_ids,
+            lookup: None,
         })
     }
 
-    fn installation_paths(&self) -> Vec<String> {
+    /// Paths to look the installation up at, in order; an owner of unknown type is tried as both.
+    fn installation_paths(&self, owner_type: Option<OwnerType>) -> Vec<String> {
+        let owner_paths = |owner: &str| match owner_type {
+            Some(OwnerType::Organization) => vec![format!("/orgs/{owner}/installation")],
+            Some(OwnerType::User) => vec![format!("/users/{owner}/installation")],
+            Some(OwnerType::Auto) | None => vec![
+                format!("/orgs/{owner}/installation"),
+                format!("/users/{owner}/installation"),
+            ],
+        };
         match self {
             Self::Enterprise { enterprise } => {
                 vec![format!("/enterprises/{enterprise}/installation")]
             }
-            Self::Owner { owner } => vec![
-                format!("/orgs/{owner}/installation"),
-                format!("/users/{owner}/installation"),
-            ],
+            Self::Owner { owner } => owner_paths(owner),
+            Self::Repository { owner, .. } => match self.lookup_repository() {
+                Some(repository) => vec![format!("/repos/{owner}/{repository}/installation")],
+                // a pattern names no repository to look the installation up by
+                None => owner_paths(owner),
+            },
+            Self::Installation { id, .. } => vec![format!("/app/installations/{id}")],
+            // discovered from the list of installations instead
+            Self::Auto { .. } => Vec::new(),
+        }
+    }
+
+    /// The repository whose installation is looked up, which is known to be accessible once found.
+    fn lookup_repository(&self) -> Option<&str> {
+        match self {
             Self::Repository {
-                owner,
                 repositories,
-            } => vec![format!("/repos/{owner}/{}/installation", repositories[0])],
+                lookup,
+                ..
+            } => repositories
+                .iter()
+                .find(|repository| !is_pattern(repository))
+                .or(lookup.as_ref())
+                .map(String::as_str),
+            _ => None,
+        }
+    }
+
+    /// Whether `repositories` names any repository the installation lookup did not already prove accessible.
+    fn names_unverified_repositories(&self) -> bool {
+        let lookup = self.lookup_repository();
+        self.repository_names().is_some_and(|repositories| {
+            repositories.iter().any(|repository| {
+                lookup.is_none_or(|lookup| !lookup.eq_ignore_ascii_case(repository))
+            })
+        })
+    }
+
+    /// The account that owns the installation, when known before looking it up.
+    fn owner(&self) -> Option<&str> {
+        match self {
+            Self::Owner { owner } | Self::Repository { owner, .. } => Some(owner),
+            Self::Auto { account, .. } => account.as_deref(),
+            Self::Enterprise { .. } | Self::Installation { .. } => None,
+        }
+    }
+
+    /// The key of the target's token in the `tokens` output.
+    fn account(&self) -> String {
+        match self {
+            Self::Enterprise { enterprise } => enterprise.clone(),
+            Self::Owner { owner } | Self::Repository { owner, .. } => owner.clone(),
+            Self::Installation { id, .. } => id.to_string(),
+            Self::Auto { account, .. } => account.clone().unwrap_or_default(),
+        }
+    }
+
+    fn repository_ids(&self) -> Option<Vec<u64>> {
+        match self {
+            Self::Repository { repository_ids, .. }
+            | Self::Installation { repository_ids, .. }
+            | Self::Auto { repository_ids, .. }
+                if !repository_ids.is_empty() =>
+            {
+                Some(repository_ids.clone())
+            }
+            _ => None,
         }
     }
 
     fn repository_names(&self) -> Option<Vec<String>> {
         match self {
             Self::Repository { repositories, .. } => Some(repositories.clone()),
+            Self::Installation { repositories, .. } | Self::Auto { repositories, .. } => {
+                repositories.clone()
+            }
             Self::Enterprise { .. } | Self::Owner { .. } => None,
         }
     }
 }
 
-fn parse_repositories(input: &str) -> Vec<String> {
+/// Splits a comma or newline-separated input.
+fn parse_list(input: &str) -> Vec<String> {
     input
         .split([',', '\n'])
         .map(str::trim)
-        .filter(|repository| !repository.is_empty())
+        .filter(|item| !item.is_empty())
         .map(ToString::to_string)
         .collect()
 }
 
+/// Whether a `repositories` entry is a glob pattern rather than a repository name.
+fn is_pattern(repository: &str) -> bool {
+    repository.contains(['*', '?'])
+}
+
+/// Matches `name` against a glob where `*` matches any run of characters and `?` any one character.
+/// Repository names are case-insensitive, so the match is too.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase().chars().collect::<Vec<_>>();
+    let name = name.to_ascii_lowercase().chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // where the last `*` was seen, and how much of the name it had consumed
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, consumed)) => {
+                    p = star + 1;
+                    n = consumed + 1;
+                    backtrack = Some((star, consumed + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OwnerType {
+    Organization,
+    User,
+    /// Read from `GET /users/{owner}` before looking the installation up.
+    Auto,
+}
+
+#[derive(Deserialize)]
+struct UserResponse {
+    #[serde(rename = "type")]
+    user_type: String,
+}
+
+/// Selects installation repositories by their topics and custom properties.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct RepositoryQuery {
+    /// Lowercase, as GitHub stores topics.
+    topics: Vec<String>,
+    /// Whether a repository needs every topic, rather than any of them.
+    match_all: bool,
+    /// Custom property names and values, all of which a repository needs.
+    properties: Vec<(String, String)>,
+}
+
+impl RepositoryQuery {
+    fn matches(&self, repository: &RepositoryResponse) -> bool {
+        let has_topic = |topic: &String| repository.topics.contains(topic);
+        let topics_match = self.topics.is_empty()
+            || if self.match_all {
+                self.topics.iter().all(has_topic)
+            } else {
+                self.topics.iter().any(has_topic)
+            };
+        let properties_match = self.properties.iter().all(|(name, value)| {
+            repository
+                .properties
+                .get(name)
+                .is_some_and(|values| values.contains(value))
+        });
+        topics_match && properties_match
+    }
+}
+
+impl fmt::Display for RepositoryQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut conditions = Vec::new();
+        if !self.topics.is_empty() {
+            let separator = if self.match_all { " and " } else { " or " };
+            conditions.push(format!("topics {}", self.topics.join(separator)));
+        }
+        if !self.properties.is_empty() {
+            let properties = self
+                .properties
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect::<Vec<_>>();
+            conditions.push(format!("properties {}", properties.join(", ")));
+        }
+        f.write_str(&conditions.join(" and "))
+    }
+}
+
+/// Expands the patterns in `repositories` and adds the repositories `query` matches.
+fn select_repositories(
+    repositories: &[String],
+    query: Option<&RepositoryQuery>,
+    available: &[RepositoryResponse],
+) -> Result<Vec<String>, Error> {
+    let names = available
+        .iter()
+        .map(|repository| repository.name.clone())
+        .collect::<Vec<_>>();
+    let missing = repositories
+        .iter()
+        .filter(|repository| {
+            !is_pattern(repository)
+                && !names
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(repository))
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(Error::from(format!(
+            "the installation cannot access {}; check that they exist and that the GitHub App is installed on them",
+            missing.join(", ")
+        )));
+    }
+    let mut selected = expand_repositories(repositories, &names)?;
+    if let Some(query) = query {
+        let matches = available
+            .iter()
+            .filter(|repository| query.matches(repository))
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            return Err(Error::from(format!(
+                "no repositories of the installation have {query}"
+            )));
+        }
+        for repository in matches {
+            if !selected
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&repository.name))
+            {
+                selected.push(repository.name.clone());
+            }
+        }
+    }
+    Ok(selected)
+}
+
+/// Replaces each pattern in `repositories` with the `available` repositories it matches.
+fn expand_repositories(
+    repositories: &[String],
+    available: &[String],
+) -> Result<Vec<String>, Error> {
+    let mut expanded: Vec<String> = Vec::new();
+    for repository in repositories {
+        let matches = if is_pattern(repository) {
+            let matches = available
+                .iter()
+                .filter(|name| glob_match(repository, name))
+                .cloned()
+                .collect::<Vec<_>>();
+            if matches.is_empty() {
+                return Err(Error::from(format!(
+                    "repository pattern '{repository}' matched no repositories of the installation"
+                )));
+            }
+            matches
+        } else {
+            vec![repository.clone()]
+        };
+        for name in matches {
+            if !expanded.iter().any(|seen| seen.eq_ignore_ascii_case(&name)) {
+                expanded.push(name);
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+/// Groups `repositories` entries by owner in order of appearance; entries without one belong to `owner`.
+fn group_repositories(
+    owner: &str,
+    repositories: Vec<String>,
+) -> Result<Vec<(String, Vec<String>)>, Error> {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for repository in repositories {
+        let repo_owner = match repository.split_once('/') {
+            Some((repo_owner, _)) => repo_owner,
+            None => owner,
+        };
+        let name = parse_repository(repo_owner, &repository)?;
+        match groups
+            .iter_mut()
+            .find(|(group, _)| group.eq_ignore_ascii_case(repo_owner))
+        {
+            Some((_, names)) => names.push(name),
+            None => groups.push((repo_owner.to_string(), vec![name])),
+        }
+    }
+    Ok(groups)
+}
+
 fn parse_repository(owner: &str, input: &str) -> Result<String, Error> {
     match input.split_once('/') {
         Some((repo_owner, repo)) if repo_owner.is_empty() || repo.is_empty() => Err(Error::from(
@@ -374,6 +1577,133 @@ fn parse_repository(owner: &str, input: &str) -> Result<String, Error> {
 struct InstallationResponse {
     id: u64,
     app_slug: String,
+    #[serde(default)]
+    suspended_at: Option<String>,
+    #[serde(default)]
+    suspended_by: Option<AccountResponse>,
+    #[serde(default)]
+    account: Option<AccountResponse>,
+    #[serde(default)]
+    target_type: String,
+    /// `all` or `selected`.
+    #[serde(default)]
+    repository_selection: String,
+}
+
+#[derive(Deserialize)]
+struct AccountResponse {
+    /// Enterprise accounts have a slug instead of a login.
+    #[serde(alias = "slug")]
+    login: String,
+}
+
+/// Fails on a suspended installation, which GitHub would otherwise answer
+/// with a 403 on the token request that reads like a permission problem.
+fn check_suspended(installation: &InstallationResponse) -> Result<(), Error> {
+    let Some(suspended_at) = &installation.suspended_at else {
+        return Ok(());
+    };
+    let account = installation
+        .account
+        .as_ref()
+        .map(|account| format!(" on {}", account.login))
+        .unwrap_or_default();
+    let suspended_by = installation
+        .suspended_by
+        .as_ref()
+        .map(|user| format!(" by {}", user.login))
+        .unwrap_or_default();
+    Err(Error::from(format!(
+        "installation {}{account} was suspended{suspended_by} at {suspended_at}; ask an owner of the account to unsuspend the GitHub App",
+        installation.id
+    )))
+}
+
+/// Picks the one installation that matches `account` and `account_type`.
+fn pick_installation(
+    installations: Vec<InstallationResponse>,
+    account: Option<&str>,
+    account_type: Option<&str>,
+) -> Result<InstallationResponse, Error> {
+    if installations.is_empty() {
+        return Err(Error::from("the GitHub App has no installations"));
+    }
+    let mut candidates = installations
+        .into_iter()
+        .filter(|installation| {
+            // the target type also covers enterprises, whose accounts have no type
+            let type_matches = account_type.is_none_or(|account_type| {
+                installation.target_type.eq_ignore_ascii_case(account_type)
+            });
+            let account_matches = account.is_none_or(|login| {
+                installation
+                    .account
+                    .as_ref()
+                    .is_some_and(|account| account.login.eq_ignore_ascii_case(login))
+            });
+            type_matches && account_matches
+        })
+        .collect::<Vec<_>>();
+    match candidates.len() {
+        1 => Ok(candidates.swap_remove(0)),
+        0 => Err(Error::from(format!(
+            "no installation of the GitHub App matches {}",
+            [account, account_type]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" ")
+        ))),
+        _ => Err(Error::from(format!(
+            "{} installations of the GitHub App match; set owner or installation-account-type to pick one of {}",
+            candidates.len(),
+            candidates
+                .iter()
+                .map(|installation| match &installation.account {
+                    Some(account) if installation.target_type.is_empty() => {
+                        format!("{} (installation {})", account.login, installation.id)
+                    }
+                    Some(account) => format!(
+                        "{} ({}, installation {})",
+                        account.login, installation.target_type, installation.id
+                    ),
+                    None => format!("installation {}", installation.id),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+/// A failed request authenticated as the App, with the message GitHub gave for it.
+enum AppRequestError {
+    /// The next path or installation may still be found.
+    NotFound(Error, String),
+    /// Another private key may still be accepted.
+    KeyRejected(Error, String),
+    /// Already logged.
+    Failed(Error),
+}
+
+impl From<Error> for AppRequestError {
+    fn from(e: Error) -> Self {
+        Self::Failed(e)
+    }
+}
+
+/// A failed attempt to create the tokens of a target.
+enum BuildError {
+    /// Another attempt may succeed.
+    Retryable(Error),
+    /// Another attempt would fail the same way, such as a suspended installation
+    /// or a repository the installation cannot access.
+    Terminal(Error),
+}
+
+impl From<Error> for BuildError {
+    fn from(e: Error) -> Self {
+        Self::Retryable(e)
+    }
 }
 
 #[derive(Serialize)]
@@ -381,19 +1711,144 @@ struct AccessTokenRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     repositories: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    repository_ids: Option<Vec<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     permissions: Option<BTreeMap<String, String>>,
 }
 
+#[derive(Deserialize)]
+struct InstallationRepositoriesResponse {
+    total_count: usize,
+    repositories: Vec<RepositoryResponse>,
+}
+
+#[derive(Deserialize)]
+struct RepositoryResponse {
+    name: String,
+    #[serde(default)]
+    topics: Vec<String>,
+    /// Custom property values, filled in from the organization when the query needs them.
+    #[serde(skip)]
+    properties: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct RepositoryPropertyValues {
+    repository_name: String,
+    properties: Vec<PropertyValue>,
+}
+
+#[derive(Deserialize)]
+struct PropertyValue {
+    property_name: String,
+    value: serde_json::Value,
+}
+
+impl PropertyValue {
+    /// Multi-select properties have several values, and unset ones none.
+    fn values(&self) -> Vec<String> {
+        match &self.value {
+            serde_json::Value::String(value) => vec![value.clone()],
+            serde_json::Value::Array(values) => values
+                .iter()
+                .filter_map(|value| value.as_str().map(ToString::to_string))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Attaches the organization's custom property values to the repositories they belong to.
+fn attach_property_values(
+    repositories: &mut [RepositoryResponse],
+    property_values: Vec<RepositoryPropertyValues>,
+) {
+    for property_values in property_values {
+        let Some(repository) = repositories.iter_mut().find(|repository| {
+            repository
+                .name
+                .eq_ignore_ascii_case(&property_values.repository_name)
+        }) else {
+            continue;
+        };
+        for property in property_values.properties {
+            let values = property.values();
+            repository.properties.insert(property.property_name, values);
+        }
+    }
+}
+
 #[derive(Deserialize)]
 struct AccessTokenResponse {
     token: String,
     expires_at: String,
+    /// The repositories a scoped token covers, including those selected by ID.
+    #[serde(default)]
+    repositories: Vec<TokenRepositoryResponse>,
+}
+
+#[derive(Deserialize)]
+struct TokenRepositoryResponse {
+    full_name: String,
+}
+
+/// GitHub scopes an installation token to at most this many repositories.
+const MAX_TOKEN_REPOSITORIES: usize = 500;
+
+/// The repository names and IDs one token is scoped to.
+type RepositoryBatch = (Option<Vec<String>>, Option<Vec<u64>>);
+
+/// Splits a repository selection into batches small enough for one token each,
+/// filling every batch with names first and IDs after them.
+fn batch_repositories(
+    mut repositories: Option<Vec<String>>,
+    mut repository_ids: Option<Vec<u64>>,
+) -> Vec<RepositoryBatch> {
+    // a duplicate would take up room in a batch, and names are case-insensitive
+    if let Some(repositories) = &mut repositories {
+        let mut seen = BTreeSet::new();
+        repositories.retain(|repository| seen.insert(repository.to_ascii_lowercase()));
+    }
+    if let Some(repository_ids) = &mut repository_ids {
+        let mut seen = BTreeSet::new();
+        repository_ids.retain(|id| seen.insert(*id));
+    }
+    let names = repositories.as_ref().map_or(0, Vec::len);
+    let ids = repository_ids.as_ref().map_or(0, Vec::len);
+    if names + ids <= MAX_TOKEN_REPOSITORIES {
+        return vec![(repositories, repository_ids)];
+    }
+    let repositories = repositories.unwrap_or_default();
+    let repository_ids = repository_ids.unwrap_or_default();
+    let (mut names, mut ids) = (repositories.as_slice(), repository_ids.as_slice());
+    let mut batches = Vec::new();
+    while !names.is_empty() || !ids.is_empty() {
+        let (batch_names, rest) = names.split_at(names.len().min(MAX_TOKEN_REPOSITORIES));
+        names = rest;
+        let room = MAX_TOKEN_REPOSITORIES - batch_names.len();
+        let (batch_ids, rest) = ids.split_at(ids.len().min(room));
+        ids = rest;
+        batches.push((
+            Some(batch_names.to_vec()),
+            (!batch_ids.is_empty()).then(|| batch_ids.to_vec()),
+        ));
+    }
+    batches
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct AccessToken {
+    /// The key of the token in the `tokens` output.
+    account: String,
     installation_id: u64,
+    /// Full names of the repositories the token is scoped to by name.
+    repositories: Vec<String>,
+    /// Index of the authorization header GitHub accepted.
+    key_index: usize,
+    authorization_header: String,
     app_slug: String,
+    target_type: String,
+    account_login: String,
     token: String,
     expires_at: String,
 }
@@ -401,51 +1856,281 @@ struct AccessToken {
 const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
 
 impl AccessTokenBuilder {
-    async fn get_installation(&self) -> Result<InstallationResponse, Error> {
-        let paths = self.target.installation_paths();
+    /// Looks up the installation, returning it with the index of the authorization header GitHub accepted.
+    async fn get_installation(
+        &self,
+        authorization_headers: &[String],
+    ) -> Result<(InstallationResponse, usize), BuildError> {
+        if let InstallationTarget::Installation {
+            id, lookup: false, ..
+        } = self.target
+        {
+            let keys = authorization_headers.len();
+            if keys > 1 {
+                warn!(
+                    "installation-id without verify-installation uses only private key #1 of {keys}; set verify-installation to true to fall back to the others"
+                );
+            }
+            // the app slug, account and target type are unknown
+            let installation = InstallationResponse {
+                id,
+                app_slug: String::new(),
+                suspended_at: None,
+                suspended_by: None,
+                account: None,
+                target_type: String::new(),
+                repository_selection: String::new(),
+            };
+            return Ok((installation, 0));
+        }
+        let (installation, key_index) = if let InstallationTarget::Auto {
+            account,
+            account_type,
+            ..
+        } = &self.target
+        {
+            let (installations, key_index) = self.list_installations(authorization_headers).await?;
+            let installation =
+                pick_installation(installations, account.as_deref(), account_type.as_deref())
+                    .map_err(BuildError::Terminal)?;
+            info!(
+                "discovered installation {} of {}",
+                installation.id,
+                installation
+                    .account
+                    .as_ref()
+                    .map_or("an unknown account", |account| account.login.as_str())
+            );
+            (installation, key_index)
+        } else {
+            let paths = self.installation_paths().await;
+            self.with_each_key(authorization_headers, async |authorization_header| {
+                for (index, path) in paths.iter().enumerate() {
+                    match self.app_get(authorization_header, path).await {
+                        Ok(res) => return res.json().await.map_err(|e| Error::new(e).into()),
+                        Err(AppRequestError::NotFound(..)) if index + 1 < paths.len() => continue,
+                        Err(e) => return Err(e),
+                    }
+                }
+                Err(Error::from("installation could not be resolved").into())
+            })
+            .await?
+        };
+        check_suspended(&installation).map_err(BuildError::Terminal)?;
+        if !installation.repository_selection.is_empty() {
+            info!(
+                "installation {} can access {} repositories",
+                installation.id, installation.repository_selection
+            );
+        }
+        Ok((installation, key_index))
+    }
+
+    async fn installation_paths(&self) -> Vec<String> {
+        let owner_type = match (self.owner_type, self.target.owner()) {
+            // only worth a request when the lookup would otherwise try both account types
+            (Some(OwnerType::Auto), Some(owner))
+                if self.target.installation_paths(None).len() > 1 =>
+            {
+                self.lookup_owner_type(owner).await
+            }
+            (owner_type, _) => owner_type,
+        };
+        self.target.installation_paths(owner_type)
+    }
 
-        for (index, path) in paths.iter().enumerate() {
-            let api = self.endpoint.uri(path)?;
+    /// Reads whether `owner` is an organization or a user, or `None` when GitHub does not say.
+    async fn lookup_owner_type(&self, owner: &str) -> Option<OwnerType> {
+        let result = async {
+            let api = self.endpoint.uri(&format!("/users/{owner}"))?;
             let res = self
                 .client
                 .get(api.to_string())
                 .header("Accept", "application/vnd.github+json")
                 .header("User-Agent", USER_AGENT)
                 .header("X-GitHub-Api-Version", "2022-11-28")
-                .header("Authorization", self.authorization_header.clone())
                 .send()
                 .await
-                .map_err(|e| Error::from(e.to_string()))?;
-
-            if let Err(e) = res.error_for_status_ref() {
-                let status = e.status();
-                let message = e.to_string();
-                let has_next_path = index + 1 < paths.len();
-                if status == Some(reqwest::StatusCode::NOT_FOUND) && has_next_path {
-                    continue;
+                .map_err(Error::new)?;
+            let res = res.error_for_status().map_err(Error::new)?;
+            res.json::<UserResponse>().await.map_err(Error::new)
+        }
+        .await;
+        match result {
+            Ok(user) if user.user_type == "Organization" => Some(OwnerType::Organization),
+            Ok(user) if user.user_type == "User" => Some(OwnerType::User),
+            // the request is unauthenticated, so rate limits and private GHES instances are expected
+            Ok(user) => {
+                info!(
+                    "owner {owner} has unexpected type {}, trying both installation paths",
+                    user.user_type
+                );
+                None
+            }
+            Err(e) => {
+                info!(
+                    "owner type of {owner} could not be read, trying both installation paths: {e}"
+                );
+                None
+            }
+        }
+    }
+
+    /// Lists every installation of the App, returning them with the index of the authorization header GitHub accepted.
+    async fn list_installations(
+        &self,
+        authorization_headers: &[String],
+    ) -> Result<(Vec<InstallationResponse>, usize), Error> {
+        self.with_each_key(authorization_headers, async |authorization_header| {
+            let mut installations = Vec::new();
+            for page in 1.. {
+                let path = format!("/app/installations?per_page=100&page={page}");
+                let res: Vec<InstallationResponse> = self
+                    .app_get(authorization_header, &path)
+                    .await?
+                    .json()
+                    .await
+                    .map_err(Error::new)?;
+                let received = res.len();
+                installations.extend(res);
+                if received < 100 {
+                    break;
                 }
+            }
+            Ok(installations)
+        })
+        .await
+    }
 
-                error!("{:?}", res.bytes().await.map_err(Error::new)?);
-                return Err(Error::from(message));
+    /// Runs `request` with each private key in turn until GitHub accepts one, returning its index.
+    async fn with_each_key<T>(
+        &self,
+        authorization_headers: &[String],
+        request: impl AsyncFn(&str) -> Result<T, AppRequestError>,
+    ) -> Result<(T, usize), Error> {
+        let keys = authorization_headers.len();
+        for (key_index, authorization_header) in authorization_headers.iter().enumerate() {
+            match request(authorization_header).await {
+                Ok(response) => {
+                    if keys > 1 {
+                        info!(
+                            "authenticated with private key #{} of {keys}",
+                            key_index + 1
+                        );
+                    }
+                    return Ok((response, key_index));
+                }
+                Err(AppRequestError::KeyRejected(..)) if key_index + 1 < keys => {
+                    warn!(
+                        "private key #{} was rejected, trying private key #{}",
+                        key_index + 1,
+                        key_index + 2
+                    );
+                }
+                Err(
+                    AppRequestError::KeyRejected(e, message)
+                    | AppRequestError::NotFound(e, message),
+                ) => {
+                    error!("{message}");
+                    return Err(e);
+                }
+                Err(AppRequestError::Failed(e)) => return Err(e),
             }
+        }
+        Err(Error::from("no private key is configured"))
+    }
+
+    /// Sends a GET request authenticated as the App.
+    async fn app_get(
+        &self,
+        authorization_header: &str,
+        path: &str,
+    ) -> Result<reqwest::Response, AppRequestError> {
+        let api = self.endpoint.uri(path)?;
+        let res = self
+            .client
+            .get(api.to_string())
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", USER_AGENT)
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .header("Authorization", authorization_header)
+            .send()
+            .await
+            .map_err(Error::new)?;
+        self.record_server_date(&res);
 
-            let res: InstallationResponse = res.json().await.map_err(Error::new)?;
+        let Err(e) = res.error_for_status_ref() else {
             return Ok(res);
+        };
+        let status = e.status();
+        let error = Error::from(e.to_string());
+        let body = res.bytes().await.map_err(Error::new)?;
+        let message = error_message(&body);
+        if status == Some(reqwest::StatusCode::NOT_FOUND) {
+            return Err(AppRequestError::NotFound(error, message));
+        }
+        if !self.record_jwt_rejection(status, &body)
+            && status == Some(reqwest::StatusCode::UNAUTHORIZED)
+        {
+            return Err(AppRequestError::KeyRejected(error, message));
+        }
+        error!("{message}");
+        Err(AppRequestError::Failed(error))
+    }
+
+    fn record_server_date(&self, res: &reqwest::Response) {
+        if self.server_clock_drift.get().is_some() {
+            return;
         }
+        let drift = res
+            .headers()
+            .get(reqwest::header::DATE)
+            .and_then(|date| date.to_str().ok())
+            .and_then(|date| clock_drift(date, unix_now()));
+        self.server_clock_drift.set(drift);
+    }
 
-        Err(Error::from("installation could not be resolved"))
+    /// Records whether GitHub rejected the JWT because of its time claims, and returns that.
+    fn record_jwt_rejection(&self, status: Option<reqwest::StatusCode>, body: &[u8]) -> bool {
+        let rejected =
+            status == Some(reqwest::StatusCode::UNAUTHORIZED) && jwt_time_claim_rejected(body);
+        if rejected {
+            self.jwt_time_rejected.set(true);
+        }
+        rejected
     }
 
-    async fn build(self) -> Result<AccessToken, Error> {
+    async fn build(self) -> Result<Vec<AccessToken>, Error> {
         let mut last_error = None;
+        let mut clock_offset = 0;
+        let mut authorization_headers =
+            self.credentials.authorization_headers(clock_offset).await?;
 
         for attempt in 1..=4 {
-            match self.try_build().await {
+            match self.try_build(&authorization_headers).await {
                 Ok(access_token) => return Ok(access_token),
-                Err(e) => {
+                Err(BuildError::Terminal(e)) => return Err(e),
+                Err(BuildError::Retryable(e)) => {
                     if attempt == 4 {
                         return Err(e);
                     }
+                    if self.jwt_time_rejected.replace(false) {
+                        let Some(drift) =
+                            drift_correction(self.server_clock_drift.get(), clock_offset)
+                        else {
+                            // the same times would be rejected again
+                            return Err(Error::from(format!(
+                                "GitHub rejected the JWT issue or expiration time, but its Date header shows no clock drift to correct; check jwt-expiration-seconds and jwt-clock-skew-seconds: {e}"
+                            )));
+                        };
+                        clock_offset = drift;
+                        warn!(
+                            "GitHub rejected the JWT issue or expiration time; GitHub's clock is {} the runner according to its Date header, please fix the runner clock",
+                            describe_drift(drift)
+                        );
+                        authorization_headers =
+                            self.credentials.authorization_headers(clock_offset).await?;
+                    }
                     warn!(
                         "failed to create token, retrying attempt {}: {e}",
                         attempt + 1
@@ -458,41 +2143,267 @@ impl AccessTokenBuilder {
         Err(last_error.unwrap_or_else(|| Error::from("failed to create token")))
     }
 
-    async fn try_build(&self) -> Result<AccessToken, Error> {
-        let installation = self.get_installation().await?;
+    /// Creates the tokens for the target, one per batch of at most 500 repositories.
+    async fn try_build(
+        &self,
+        authorization_headers: &[String],
+    ) -> Result<Vec<AccessToken>, BuildError> {
+        let (installation, key_index) = self.get_installation(authorization_headers).await?;
         let installation_id = installation.id;
-        let path = format!("/app/installations/{}/access_tokens", installation_id);
-        let api = self.endpoint.uri(&path)?;
+        let authorization_header = &authorization_headers[key_index];
+        let installation_account = installation.account.map(|account| account.login);
+        let target_type = installation.target_type;
+        let owner = self.target.owner().or(installation_account.as_deref());
 
-        let body = AccessTokenRequest {
-            repositories: self.target.repository_names(),
-            permissions: self.permissions.clone(),
+        let repositories = match self.selected_repositories.get() {
+            Some(repositories) => repositories.clone(),
+            None => {
+                let repositories = self
+                    .select_repositories(installation_id, authorization_header, owner)
+                    .await?;
+                self.selected_repositories
+                    .get_or_init(|| repositories.clone());
+                repositories
+            }
         };
-        let res = self
-            .client
-            .post(api.to_string())
-            .header("Accept", "application/vnd.github+json")
-            .header("User-Agent", USER_AGENT)
-            .header("X-GitHub-Api-Version", "2022-11-28")
-            .header("Authorization", self.authorization_header.clone())
-            .json(&body)
-            .send()
-            .await
-            .map_err(Error::new)?;
+        let account = match (&self.target, &installation_account) {
+            (InstallationTarget::Auto { .. }, Some(account)) => account.clone(),
+            _ => self.target.account(),
+        };
+        let batches = batch_repositories(repositories, self.target.repository_ids());
+        if batches.len() > 1 {
+            info!(
+                "more than {MAX_TOKEN_REPOSITORIES} repositories are selected, creating {} tokens",
+                batches.len()
+            );
+        }
 
-        if let Err(e) = res.error_for_status_ref() {
-            error!("{:?}", res.bytes().await.map_err(Error::new)?);
-            Err(Error::from(e.to_string()))
-        } else {
-            let res: AccessTokenResponse = res.json().await.map_err(Error::new)?;
-            add_mask(&res.token);
-            Ok(AccessToken {
+        let mut access_tokens = Vec::new();
+        for (repositories, repository_ids) in batches {
+            let full_names = match (owner, &repositories) {
+                (Some(owner), Some(repositories)) => repositories
+                    .iter()
+                    .map(|repository| format!("{owner}/{repository}"))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            let body = AccessTokenRequest {
+                // a target selected only by ID has no names to send
+                repositories: repositories
+                    .filter(|repositories| !repositories.is_empty() || repository_ids.is_none()),
+                repository_ids,
+                permissions: self.permissions.clone(),
+            };
+            let res = match self
+                .create_access_token(installation_id, authorization_header, &body)
+                .await
+            {
+                Ok(res) => res,
+                Err(e) => {
+                    revoke_tokens(&self.endpoint, access_tokens).await;
+                    return Err(e.into());
+                }
+            };
+            access_tokens.push(AccessToken {
+                account: account.clone(),
                 installation_id,
-                app_slug: installation.app_slug,
+                repositories: if res.repositories.is_empty() {
+                    full_names
+                } else {
+                    res.repositories
+                        .into_iter()
+                        .map(|repository| repository.full_name)
+                        .collect()
+                },
+                key_index,
+                authorization_header: authorization_header.clone(),
+                app_slug: installation.app_slug.clone(),
+                target_type: target_type.clone(),
+                account_login: installation_account.clone().unwrap_or_default(),
                 token: res.token,
                 expires_at: res.expires_at,
-            })
+            });
+        }
+        Ok(access_tokens)
+    }
+
+    /// The repositories to scope the token to, with patterns and `repository_query` resolved.
+    async fn select_repositories(
+        &self,
+        installation_id: u64,
+        authorization_header: &str,
+        owner: Option<&str>,
+    ) -> Result<Option<Vec<String>>, BuildError> {
+        // listing the installation's repositories also confirms every named one is accessible
+        let repositories = match self.target.repository_names() {
+            Some(repositories)
+                if self.repository_query.is_some()
+                    || self.target.names_unverified_repositories() =>
+            {
+                repositories
+            }
+            repositories => return Ok(repositories),
+        };
+        let available = self
+            .list_repositories(installation_id, authorization_header, owner)
+            .await?;
+        let repositories =
+            select_repositories(&repositories, self.repository_query.as_ref(), &available)
+                .map_err(BuildError::Terminal)?;
+        info!("resolved repositories: {}", repositories.join(", "));
+        Ok(Some(repositories))
+    }
+
+    async fn create_access_token(
+        &self,
+        installation_id: u64,
+        authorization_header: &str,
+        body: &AccessTokenRequest,
+    ) -> Result<AccessTokenResponse, Error> {
+        let path = format!("/app/installations/{}/access_tokens", installation_id);
+        let api = self.endpoint.uri(&path)?;
+        let res = self
+            .client
+            .post(api.to_string())
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", USER_AGENT)
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .header("Authorization", authorization_header)
+            .json(body)
+            .send()
+            .await
+            .map_err(Error::new)?;
+        self.record_server_date(&res);
+
+        if let Err(e) = res.error_for_status_ref() {
+            let body = res.bytes().await.map_err(Error::new)?;
+            self.record_jwt_rejection(e.status(), &body);
+            error!("{}", error_message(&body));
+            Err(Error::from(e.to_string()))
+        } else {
+            let res: AccessTokenResponse = res.json().await.map_err(Error::new)?;
+            add_mask(&res.token);
+            Ok(res)
+        }
+    }
+
+    /// Lists every repository of the installation,
+    /// using a short-lived token that can only read metadata.
+    async fn list_repositories(
+        &self,
+        installation_id: u64,
+        authorization_header: &str,
+        owner: Option<&str>,
+    ) -> Result<Vec<RepositoryResponse>, Error> {
+        let property_owner = match &self.repository_query {
+            // with installation: auto, the owner is the discovered account
+            Some(query) if !query.properties.is_empty() => Some(owner.ok_or_else(|| {
+                Error::from("repository-properties needs owner to find the organization")
+            })?),
+            _ => None,
+        };
+        let mut permissions = BTreeMap::from([("metadata".to_string(), "read".to_string())]);
+        if property_owner.is_some() {
+            permissions.insert(
+                "organization_custom_properties".to_string(),
+                "read".to_string(),
+            );
+        }
+        let body = AccessTokenRequest {
+            repositories: None,
+            repository_ids: None,
+            permissions: Some(permissions),
+        };
+        let listing_token = self
+            .create_access_token(installation_id, authorization_header, &body)
+            .await?;
+
+        let repositories = async {
+            let mut repositories = self
+                .list_installation_repositories(&listing_token.token)
+                .await?;
+            if let Some(owner) = property_owner {
+                let property_values = self
+                    .list_property_values(&listing_token.token, owner)
+                    .await?;
+                attach_property_values(&mut repositories, property_values);
+            }
+            Ok(repositories)
+        }
+        .await;
+        let revoked = RemoveAccessTokenRequest {
+            endpoint: self.endpoint.clone(),
+            token: listing_token.token,
+            expires_at: listing_token.expires_at,
+            skip_token_revoke: false,
+            client: self.client.clone(),
+        }
+        .execute()
+        .await;
+        if let Err(e) = revoked {
+            warn!("revocation of the repository listing token failed: {e}");
+        }
+        repositories
+    }
+
+    async fn list_installation_repositories(
+        &self,
+        token: &str,
+    ) -> Result<Vec<RepositoryResponse>, Error> {
+        let mut repositories = Vec::new();
+        for page in 1.. {
+            let path = format!("/installation/repositories?per_page=100&page={page}");
+            let res: InstallationRepositoriesResponse = self.get_with_token(token, &path).await?;
+            let received = res.repositories.len();
+            repositories.extend(res.repositories);
+            if received == 0 || repositories.len() >= res.total_count {
+                break;
+            }
+        }
+        Ok(repositories)
+    }
+
+    async fn list_property_values(
+        &self,
+        token: &str,
+        owner: &str,
+    ) -> Result<Vec<RepositoryPropertyValues>, Error> {
+        let mut property_values = Vec::new();
+        for page in 1.. {
+            let path = format!("/orgs/{owner}/properties/values?per_page=100&page={page}");
+            let res: Vec<RepositoryPropertyValues> = self.get_with_token(token, &path).await?;
+            let received = res.len();
+            property_values.extend(res);
+            if received < 100 {
+                break;
+            }
+        }
+        Ok(property_values)
+    }
+
+    async fn get_with_token<T: serde::de::DeserializeOwned>(
+        &self,
+        token: &str,
+        path: &str,
+    ) -> Result<T, Error> {
+        let api = self.endpoint.uri(path)?;
+        let res = self
+            .client
+            .get(api.to_string())
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", USER_AGENT)
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .header("Authorization", access_token_authorization_header(token))
+            .send()
+            .await
+            .map_err(Error::new)?;
+
+        if let Err(e) = res.error_for_status_ref() {
+            let body = res.bytes().await.map_err(Error::new)?;
+            error!("{}", error_message(&body));
+            return Err(Error::from(e.to_string()));
         }
+        res.json().await.map_err(Error::new)
     }
 }
 
@@ -506,7 +2417,8 @@ struct RemoveAccessTokenRequest {
 
 impl RemoveAccessTokenRequest {
     async fn execute(self) -> Result<(), Error> {
-        if self.skip_token_revoke || token_expired(&self.expires_at) {
+        // jwt-only runs have no token to revoke
+        if self.skip_token_revoke || self.token.is_empty() || token_expired(&self.expires_at) {
             return Ok(());
         }
 
@@ -534,6 +2446,24 @@ impl RemoveAccessTokenRequest {
     }
 }
 
+/// Revokes tokens that will never be output, regardless of skip-token-revoke.
+async fn revoke_tokens(endpoint: &ApiEndpoint, access_tokens: Vec<AccessToken>) {
+    for access_token in access_tokens {
+        let revoked = RemoveAccessTokenRequest {
+            endpoint: endpoint.clone(),
+            token: access_token.token,
+            expires_at: access_token.expires_at,
+            skip_token_revoke: false,
+            client: reqwest::Client::new(),
+        }
+        .execute()
+        .await;
+        if let Err(e) = revoked {
+            warn!("token revocation failed: {e}");
+        }
+    }
+}
+
 fn token_expired(expires_at: &str) -> bool {
     chrono::DateTime::parse_from_rfc3339(expires_at)
         .map(|expires_at| expires_at.timestamp() <= unix_now())
@@ -544,6 +2474,12 @@ fn access_token_authorization_header(token: &str) -> String {
     format!("Bearer {token}")
 }
 
+fn bare_jwt(authorization_header: &str) -> &str {
+    authorization_header
+        .strip_prefix("Bearer ")
+        .unwrap_or(authorization_header)
+}
+
 fn permissions_from_inputs() -> Option<BTreeMap<String, String>> {
     permissions_from_vars(env::vars())
 }
@@ -573,6 +2509,45 @@ fn permissions_from_vars(
     }
 }
 
+/// Returns how many seconds the clock behind an HTTP `Date` header is ahead of `now`.
+fn clock_drift(date: &str, now: i64) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc2822(date)
+        .ok()
+        .map(|date| date.timestamp() - now)
+}
+
+/// Returns the clock offset to retry with, if the measured drift differs from the one already applied.
+fn drift_correction(measured: Option<i64>, applied: i64) -> Option<i64> {
+    measured.filter(|drift| *drift != applied)
+}
+
+fn describe_drift(drift: i64) -> String {
+    if drift < 0 {
+        format!("{} seconds behind", -drift)
+    } else {
+        format!("{drift} seconds ahead of")
+    }
+}
+
+/// GitHub names the offending claim when it rejects a JWT,
+/// e.g. "'Issued at' claim ('iat') must be an Integer representing a time in the past".
+#[derive(Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+/// The `message` of a GitHub error response, or the body itself when it is not one.
+fn error_message(body: &[u8]) -> String {
+    serde_json::from_slice::<ErrorResponse>(body)
+        .map(|response| response.message)
+        .unwrap_or_else(|_| String::from_utf8_lossy(body).into_owned())
+}
+
+fn jwt_time_claim_rejected(body: &[u8]) -> bool {
+    let body = String::from_utf8_lossy(body);
+    body.contains("('iat')") || body.contains("('exp')")
+}
+
 fn unix_now() -> i64 {
     chrono::Utc::now().timestamp()
 }
@@ -581,171 +2556,1386 @@ fn encode_base64_url(src: &[u8]) -> String {
     Base64UrlUnpadded::encode_string(src)
 }
 
-#[cfg(all(test, target_arch = "wasm32"))]
-mod tests {
-    use super::*;
-    use wasm_bindgen_test::wasm_bindgen_test;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn input() -> Input {
+        Input {
+            app_id: "legacy-app-id".to_string(),
+            client_id: "client-id".to_string(),
+            private_key: "private-key".to_string(),
+            private_key_path: String::new(),
+            private_key_passphrase: String::new(),
+            signer_command: String::new(),
+            vault_address: String::new(),
+            vault_token: String::new(),
+            vault_transit_mount: "transit".to_string(),
+            vault_transit_key: String::new(),
+            expected_key_fingerprint: String::new(),
+            jwt_expiration_seconds: "60".to_string(),
+            jwt_clock_skew_seconds: "60".to_string(),
+            output_jwt: false,
+            jwt_only: false,
+            github_api_url: "https://api.github.com".to_string(),
+            endpoint: String::new(),
+            owner: String::new(),
+            owner_type: String::new(),
+            repositories: String::new(),
+            repository_ids: String::new(),
+            repository_topics: String::new(),
+            repository_topics_match: "all".to_string(),
+            repository_properties: String::new(),
+            enterprise: String::new(),
+            installation_id: String::new(),
+            verify_installation: false,
+            installation: String::new(),
+            installation_account_type: String::new(),
+            skip_token_revoke: false,
+            repo: "owner/current".to_string(),
+            repo_owner: "owner".to_string(),
+            repo_id: String::new(),
+        }
+    }
+
+    fn output() -> Output {
+        Output {
+            token: "ghs_a".to_string(),
+            tokens: r#"{"octo-org":"ghs_a"}"#.to_string(),
+            token_batches: String::new(),
+            repository_tokens: r#"{"octo-org/repo":"ghs_a"}"#.to_string(),
+            installation_id: "123".to_string(),
+            app_slug: "octo-app".to_string(),
+            target_type: "Organization".to_string(),
+            account_login: "octo-org".to_string(),
+            key_fingerprint: "SHA256:fingerprint".to_string(),
+            jwt: String::new(),
+            expires_at: "2999-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    fn repository(name: &str, topics: &[&str]) -> RepositoryResponse {
+        RepositoryResponse {
+            name: name.to_string(),
+            topics: topics.iter().map(ToString::to_string).collect(),
+            properties: BTreeMap::new(),
+        }
+    }
+
+    fn installations() -> Vec<InstallationResponse> {
+        serde_json::from_value(serde_json::json!([
+            {"id": 1, "app_slug": "octo-app", "target_type": "Organization", "account": {"login": "octo-org", "type": "Organization"}},
+            {"id": 2, "app_slug": "octo-app", "target_type": "User", "account": {"login": "octocat", "type": "User"}},
+            {"id": 3, "app_slug": "octo-app", "target_type": "Enterprise", "account": {"slug": "octo-enterprise", "name": "Octo"}},
+        ]))
+        .unwrap()
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn parses_default_github_api_url() {
+        let endpoint = ApiEndpoint::from_inputs("https://api.github.com", "").unwrap();
+
+        assert_eq!(
+            endpoint.uri("/repos/owner/repo/installation").unwrap(),
+            "https://api.github.com/repos/owner/repo/installation"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn preserves_github_enterprise_api_base_path() {
+        let endpoint = ApiEndpoint::from_inputs("https://github.example.com/api/v3/", "").unwrap();
+
+        assert_eq!(
+            endpoint
+                .uri("/app/installations/123/access_tokens")
+                .unwrap(),
+            "https://github.example.com/api/v3/app/installations/123/access_tokens"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn endpoint_alias_overrides_github_api_url() {
+        let endpoint = ApiEndpoint::from_inputs(
+            "https://api.github.com",
+            "https://github.example.com/api/v3",
+        )
+        .unwrap();
+
+        assert_eq!(
+            endpoint.uri("/installation/token").unwrap(),
+            "https://github.example.com/api/v3/installation/token"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_github_api_url_without_authority() {
+        assert!(ApiEndpoint::from_inputs("https:///api/v3", "").is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn client_id_prefers_new_input_over_deprecated_app_id() {
+        let mut input = input();
+        input.app_id = "legacy-app-id".to_string();
+        input.client_id = "client-id".to_string();
+
+        assert_eq!(input.client_id().unwrap(), "client-id");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn client_id_falls_back_to_deprecated_app_id() {
+        let mut input = input();
+        input.app_id = "legacy-app-id".to_string();
+        input.client_id = " ".to_string();
+
+        assert_eq!(input.client_id().unwrap(), "legacy-app-id");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn client_id_rejects_blank_aliases() {
+        let mut input = input();
+        input.app_id = " ".to_string();
+        input.client_id = String::new();
+
+        assert_eq!(
+            input.client_id().unwrap_err().to_string(),
+            "client-id or deprecated app-id must be set to a non-empty string"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn private_key_normalizes_escaped_newlines() {
+        let mut input = input();
+        input.private_key = "-----BEGIN KEY-----\\nabc\\n-----END KEY-----".to_string();
+
+        let private_keys = input.private_keys().unwrap();
+        let private_key = &private_keys[0];
+        assert_eq!(
+            private_key.pem,
+            "-----BEGIN KEY-----\nabc\n-----END KEY-----"
+        );
+        assert_eq!(private_key.source, "private-key");
+        assert_eq!(private_key.format, KeyFormat::Pem);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn private_key_rejects_blank_value() {
+        let mut input = input();
+        input.private_key = " ".to_string();
+
+        assert_eq!(
+            input.private_keys().err().unwrap().to_string(),
+            "private-key, private-key-path, signer-command, or vault-address must be set to a non-empty string"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn private_key_rejects_both_inline_key_and_path() {
+        let mut input = input();
+        input.private_key_path = "/run/secrets/app.pem".to_string();
+
+        assert_eq!(
+            input.private_keys().err().unwrap().to_string(),
+            "private-key and private-key-path cannot be used together"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn private_key_accepts_multiple_pem_blocks_for_rotation() {
+        let mut input = input();
+        input.private_key =
+            "-----BEGIN KEY-----\nnew\n-----END KEY-----\n-----BEGIN KEY-----\nold\n-----END KEY-----"
+                .to_string();
+
+        let private_keys = input.private_keys().unwrap();
+
+        assert_eq!(
+            private_keys
+                .iter()
+                .map(|key| (key.pem.as_str(), key.source.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "-----BEGIN KEY-----\nnew\n-----END KEY-----",
+                    "private-key key #1"
+                ),
+                (
+                    "-----BEGIN KEY-----\nold\n-----END KEY-----",
+                    "private-key key #2"
+                ),
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn private_key_passphrase_is_optional() {
+        let mut input = input();
+        assert_eq!(input.private_key_passphrase(), None);
+
+        input.private_key_passphrase = "passphrase".to_string();
+        assert_eq!(
+            input.private_key_passphrase().as_deref(),
+            Some("passphrase")
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn private_key_load_reports_key_problems() {
+        let key = PrivateKey::new(
+            include_str!("testdata/public-key.pem"),
+            "private-key".to_string(),
+        );
+
+        assert_eq!(
+            key.load(None).err().unwrap().to_string(),
+            "private-key is a public key, not a private key"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn signer_command_replaces_private_key() {
+        let mut input = input();
+        input.private_key = String::new();
+        input.signer_command = " ./sign.sh ".to_string();
+
+        assert_eq!(
+            input.signer_command().unwrap().as_deref(),
+            Some("./sign.sh")
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn signer_command_rejects_private_key() {
+        let mut input = input();
+        input.signer_command = "./sign.sh".to_string();
+
+        assert_eq!(
+            input.signer_command().unwrap_err().to_string(),
+            "signer-command cannot be used with private-key or private-key-path"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn vault_transit_replaces_private_key() {
+        let mut input = input();
+        input.private_key = String::new();
+        input.vault_address = "https://vault.example.com:8200".to_string();
+        input.vault_token = "s.token".to_string();
+        input.vault_transit_key = "github-app".to_string();
+
+        assert!(input.vault_transit().unwrap().is_some());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn vault_transit_rejects_private_key() {
+        let mut input = input();
+        input.vault_address = "https://vault.example.com:8200".to_string();
+
+        assert_eq!(
+            input.vault_transit().err().unwrap().to_string(),
+            "vault-address cannot be used with private-key, private-key-path, or signer-command"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn vault_transit_requires_key_and_token() {
+        let mut input = input();
+        input.private_key = String::new();
+        input.vault_address = "https://vault.example.com:8200".to_string();
+        input.vault_transit_key = "github-app".to_string();
+
+        assert_eq!(
+            input.vault_transit().err().unwrap().to_string(),
+            "vault-transit-key and vault-token must be set to use vault-address"
+        );
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn builds_jwt_with_signer_command() {
+        let header = JwtBuilder {
+            payload: Payload {
+                iss: "client-id".to_string(),
+                iat: 0,
+                exp: 60,
+            },
+            signer: JwtSigner::Command("printf signature".to_string()),
+        }
+        .build_authorization_header()
+        .await
+        .unwrap();
+
+        assert!(header.ends_with(&format!(".{}", encode_base64_url(b"signature"))));
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn falls_back_to_next_private_key_when_github_rejects_one() {
+        use crate::stand_in::{Reply, StandInServer};
+
+        let github = StandInServer::start(vec![
+            Reply::json(401, r#"{"message":"A JSON web token could not be decoded"}"#),
+            Reply::json(
+                200,
+                r#"{"id":123,"app_slug":"octo-app","target_type":"Organization","account":{"login":"octo-org","type":"Organization"}}"#,
+            ),
+            Reply::json(
+                201,
+                r#"{"token":"ghs_b","expires_at":"2999-01-01T00:00:00Z"}"#,
+            ),
+        ])
+        .await;
+        let mut input = input();
+        input.private_key = format!(
+            "{}{}",
+            include_str!("testdata/private-key.pem"),
+            include_str!("testdata/second-private-key.pem")
+        );
+        input.github_api_url = github.address.clone();
+        input.owner = "octo-org".to_string();
+        input.installation_id = "123".to_string();
+        input.verify_installation = true;
+
+        let output = GhTokenGen::main(input).await.unwrap();
+
+        assert_eq!(output.token, "ghs_b");
+        // openssl pkey -in second-private-key.pem -pubout -outform DER | openssl sha256 -binary | openssl base64
+        assert_eq!(
+            output.key_fingerprint,
+            "SHA256:mSZzrXxyn3AugHDT1vQj9w6Lh46rxrC+q1ZT9xheLr4="
+        );
+        let requests = github.requests.borrow();
+        assert_eq!(
+            requests
+                .iter()
+                .map(|request| format!("{} {}", request.method, request.url))
+                .collect::<Vec<_>>(),
+            vec![
+                "GET /app/installations/123",
+                "GET /app/installations/123",
+                "POST /app/installations/123/access_tokens",
+            ]
+        );
+        // the token is created with the key GitHub accepted
+        assert_ne!(
+            requests[0].header("authorization"),
+            requests[1].header("authorization")
+        );
+        assert_eq!(
+            requests[1].header("authorization"),
+            requests[2].header("authorization")
+        );
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn retries_with_github_clock_when_jwt_time_is_rejected() {
+        use crate::stand_in::{Reply, Request, StandInServer};
+
+        let github_now = chrono::Utc::now() + chrono::TimeDelta::hours(1);
+        let github = StandInServer::start(vec![
+            Reply::json(
+                401,
+                r#"{"message":"'Issued at' claim ('iat') must be an Integer representing a time in the past"}"#,
+            )
+            .header("Date", github_now.to_rfc2822()),
+            Reply::json(
+                200,
+                r#"{"id":123,"app_slug":"octo-app","target_type":"Organization","account":{"login":"octo-org","type":"Organization"}}"#,
+            ),
+            Reply::json(
+                201,
+                r#"{"token":"ghs_a","expires_at":"2999-01-01T00:00:00Z"}"#,
+            ),
+        ])
+        .await;
+        let mut input = input();
+        input.private_key = include_str!("testdata/private-key.pem").to_string();
+        input.github_api_url = github.address.clone();
+        input.owner = "octo-org".to_string();
+        input.installation_id = "123".to_string();
+        input.verify_installation = true;
+
+        let output = GhTokenGen::main(input).await.unwrap();
+
+        assert_eq!(output.token, "ghs_a");
+        let iat = |request: &Request| {
+            let authorization_header = request.header("authorization");
+            let payload = bare_jwt(&authorization_header).split('.').nth(1).unwrap();
+            let claims: serde_json::Value =
+                serde_json::from_slice(&Base64UrlUnpadded::decode_vec(payload).unwrap()).unwrap();
+            claims["iat"].as_i64().unwrap()
+        };
+        let requests = github.requests.borrow();
+        assert_eq!(requests.len(), 3);
+        // the retried JWT is issued by GitHub's clock, an hour ahead of the runner
+        let shift = iat(&requests[1]) - iat(&requests[0]);
+        assert!((3595..=3605).contains(&shift), "iat shifted by {shift}");
+    }
+
+    /// Polls a future that completes without waiting, such as signing with the `rsa` backend.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn poll_once<F: std::future::Future>(future: F) -> F::Output {
+        use std::task::{Context, Poll, Waker};
+
+        match std::pin::pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("the future did not complete on the first poll"),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn builds_jwt_with_rsa_backend() {
+        use rsa::{
+            pkcs1v15::{Signature, VerifyingKey},
+            pkcs8::DecodePrivateKey,
+            sha2::Sha256,
+            signature::Verifier,
+        };
+
+        let pkey = load_pkey(include_str!("testdata/private-key.pem"), None).unwrap();
+        let header = poll_once(
+            JwtBuilder {
+                payload: Payload {
+                    iss: "client-id".to_string(),
+                    iat: 0,
+                    exp: 60,
+                },
+                signer: JwtSigner::PrivateKey(pkey.clone()),
+            }
+            .build_authorization_header(),
+        )
+        .unwrap();
+
+        let (signing_input, sig) = bare_jwt(&header).rsplit_once('.').unwrap();
+        let sig = Base64UrlUnpadded::decode_vec(sig).unwrap();
+        let public_key = rsa::RsaPrivateKey::from_pkcs8_der(&pkey)
+            .unwrap()
+            .to_public_key();
+        assert!(VerifyingKey::<Sha256>::new(public_key)
+            .verify(
+                signing_input.as_bytes(),
+                &Signature::try_from(sig.as_slice()).unwrap()
+            )
+            .is_ok());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn parses_expected_key_fingerprints() {
+        let mut input = input();
+        input.expected_key_fingerprint = "SHA256:new=,\n SHA256:old= \n".to_string();
+
+        assert_eq!(
+            input.expected_key_fingerprints(),
+            vec!["SHA256:new=".to_string(), "SHA256:old=".to_string()]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn verifies_key_fingerprint_with_or_without_prefix() {
+        assert!(verify_key_fingerprint("private-key", "SHA256:abc=", &[]).is_ok());
+        assert!(
+            verify_key_fingerprint("private-key", "SHA256:abc=", &["abc=".to_string()]).is_ok()
+        );
+        assert_eq!(
+            verify_key_fingerprint("private-key", "SHA256:abc=", &["SHA256:xyz=".to_string()])
+                .unwrap_err()
+                .to_string(),
+            "private-key has fingerprint SHA256:abc=, which does not match expected-key-fingerprint"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn jwt_lifetime_defaults_to_one_minute_each_way() {
+        assert_eq!(
+            input().jwt_lifetime().unwrap(),
+            JwtLifetime {
+                expiration: 60,
+                clock_skew: 60
+            }
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn jwt_payload_uses_configured_lifetime() {
+        let payload = GhTokenGen::create_payload(
+            "client-id".to_string(),
+            JwtLifetime {
+                expiration: 570,
+                clock_skew: 30,
+            },
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(payload.exp - payload.iat, 600);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn jwt_payload_applies_clock_offset() {
+        let lifetime = JwtLifetime {
+            expiration: 60,
+            clock_skew: 60,
+        };
+        let payload = GhTokenGen::create_payload("client-id".to_string(), lifetime, 3600).unwrap();
+
+        assert!(payload.iat >= unix_now() + 3600 - 60);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn measures_clock_drift_from_date_header() {
+        assert_eq!(
+            clock_drift("Sun, 06 Nov 1994 08:49:37 GMT", 784111777 - 90),
+            Some(90)
+        );
+        assert_eq!(clock_drift("not a date", 0), None);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn corrects_only_measured_clock_drift() {
+        assert_eq!(drift_correction(Some(90), 0), Some(90));
+        assert_eq!(drift_correction(Some(-90), 0), Some(-90));
+        // nothing was measured, or the correction did not help
+        assert_eq!(drift_correction(None, 0), None);
+        assert_eq!(drift_correction(Some(0), 0), None);
+        assert_eq!(drift_correction(Some(90), 90), None);
+
+        assert_eq!(describe_drift(90), "90 seconds ahead of");
+        assert_eq!(describe_drift(-90), "90 seconds behind");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn detects_jwt_time_claim_rejections() {
+        assert!(jwt_time_claim_rejected(
+            br#"{"message":"'Issued at' claim ('iat') must be an Integer representing a time in the past"}"#
+        ));
+        assert!(jwt_time_claim_rejected(
+            br#"{"message":"'Expiration time' claim ('exp') is too far in the future"}"#
+        ));
+        assert!(!jwt_time_claim_rejected(
+            br#"{"message":"A JSON web token could not be decoded"}"#
+        ));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn jwt_lifetime_rejects_expiration_beyond_github_limit() {
+        let mut input = input();
+        input.jwt_expiration_seconds = "601".to_string();
+        assert_eq!(
+            input.jwt_lifetime().unwrap_err().to_string(),
+            "jwt-expiration-seconds must be an integer between 1 and 600"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn jwt_lifetime_rejects_window_beyond_github_limit() {
+        let mut input = input();
+        input.jwt_expiration_seconds = "600".to_string();
+        assert_eq!(
+            input.jwt_lifetime().unwrap_err().to_string(),
+            "jwt-expiration-seconds plus jwt-clock-skew-seconds must be at most 600, got 660"
+        );
+
+        input.jwt_clock_skew_seconds = "0".to_string();
+        assert!(input.jwt_lifetime().is_ok());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn jwt_lifetime_rejects_negative_clock_skew() {
+        let mut input = input();
+        input.jwt_clock_skew_seconds = "-1".to_string();
+        assert_eq!(
+            input.jwt_lifetime().unwrap_err().to_string(),
+            "jwt-clock-skew-seconds must be an integer between 0 and 600"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn resolves_current_repository_target_by_default() {
+        let target = InstallationTarget::resolve(&input()).unwrap();
+
+        assert_eq!(
+            target.installation_paths(None),
+            vec!["/repos/owner/current/installation".to_string()]
+        );
+        assert_eq!(target.repository_names(), Some(vec!["current".to_string()]));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn resolves_current_repository_by_id_when_available() {
+        let mut input = input();
+        input.repo_id = "123456".to_string();
+
+        let target = InstallationTarget::resolve(&input).unwrap();
+
+        assert_eq!(
+            target.installation_paths(None),
+            vec!["/repos/owner/current/installation".to_string()]
+        );
+        assert_eq!(target.repository_names(), Some(Vec::new()));
+        assert_eq!(target.repository_ids(), Some(vec![123456]));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn resolves_repository_ids_against_owner() {
+        let mut input = input();
+        input.owner = "octo-org".to_string();
+        input.repositories = "repo1".to_string();
+        input.repository_ids = "1, 2\n3".to_string();
+
+        let target = InstallationTarget::resolve(&input).unwrap();
+
+        assert_eq!(
+            target.installation_paths(None),
+            vec!["/repos/octo-org/repo1/installation".to_string()]
+        );
+        assert_eq!(target.repository_names(), Some(vec!["repo1".to_string()]));
+        assert_eq!(target.repository_ids(), Some(vec![1, 2, 3]));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_invalid_repository_ids() {
+        let mut input = input();
+        input.repository_ids = "repo1".to_string();
+
+        assert_eq!(
+            input.repository_ids().unwrap_err().to_string(),
+            "invalid repository ID 'repo1'"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn serializes_repository_ids_in_access_token_request() {
+        let body = AccessTokenRequest {
+            repositories: None,
+            repository_ids: Some(vec![123456]),
+            permissions: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(body).unwrap(),
+            serde_json::json!({ "repository_ids": [123456] })
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn resolves_owner_target_without_repository_scope() {
+        let mut input = input();
+        input.owner = "octo-org".to_string();
+
+        let target = InstallationTarget::resolve(&input).unwrap();
+
+        assert_eq!(
+            target.installation_paths(None),
+            vec![
+                "/orgs/octo-org/installation".to_string(),
+                "/users/octo-org/installation".to_string()
+            ]
+        );
+        assert_eq!(target.repository_names(), None);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn looks_up_owner_installation_by_owner_type() {
+        let mut input = input();
+        input.owner = "octocat".to_string();
+        input.owner_type = "User".to_string();
+
+        let target = InstallationTarget::resolve(&input).unwrap();
+
+        assert_eq!(
+            target.installation_paths(input.owner_type().unwrap()),
+            vec!["/users/octocat/installation".to_string()]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_owner_type_with_multiple_owners() {
+        let mut input = input();
+        input.owner = "octo-org, octocat".to_string();
+        input.owner_type = "organization".to_string();
+
+        assert!(InstallationTarget::resolve_all(&input).is_err());
+
+        input.owner_type = "auto".to_string();
+        assert_eq!(InstallationTarget::resolve_all(&input).unwrap().len(), 2);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_unknown_owner_type() {
+        let mut input = input();
+        input.owner_type = "team".to_string();
+
+        assert_eq!(
+            input.owner_type().err().unwrap().to_string(),
+            "owner-type must be 'organization', 'user', or 'auto', got 'team'"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn resolves_repository_list_against_owner() {
+        let mut input = input();
+        input.owner = "octo-org".to_string();
+        input.repositories = "repo1, octo-org/repo2\nrepo3".to_string();
+
+        let target = InstallationTarget::resolve(&input).unwrap();
+
+        assert_eq!(
+            target.installation_paths(None),
+            vec!["/repos/octo-org/repo1/installation".to_string()]
+        );
+        assert_eq!(
+            target.repository_names(),
+            Some(vec![
+                "repo1".to_string(),
+                "repo2".to_string(),
+                "repo3".to_string()
+            ])
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn resolves_one_target_per_owner() {
+        let mut input = input();
+        input.owner = "org-a, org-b\norg-c".to_string();
+
+        let accounts = InstallationTarget::resolve_all(&input)
+            .unwrap()
+            .iter()
+            .map(InstallationTarget::account)
+            .collect::<Vec<_>>();
+
+        assert_eq!(accounts, vec!["org-a", "org-b", "org-c"]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn resolves_duplicate_owners_once() {
+        let mut input = input();
+        input.owner = "org-a, org-b, ORG-A".to_string();
+
+        let accounts = InstallationTarget::resolve_all(&input)
+            .unwrap()
+            .iter()
+            .map(InstallationTarget::account)
+            .collect::<Vec<_>>();
+
+        assert_eq!(accounts, vec!["org-a", "org-b"]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn resolves_single_owner_from_owner_list() {
+        let mut input = input();
+        input.owner = "octo-org,".to_string();
+
+        let targets = InstallationTarget::resolve_all(&input).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(
+            targets[0].installation_paths(Some(OwnerType::Organization)),
+            vec!["/orgs/octo-org/installation".to_string()]
+        );
+
+        input.owner = "octo-org, OCTO-ORG".to_string();
+        input.repositories = "repo1".to_string();
+        let targets = InstallationTarget::resolve_all(&input).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(
+            targets[0].repository_names(),
+            Some(vec!["repo1".to_string()])
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn resolves_single_target_for_single_owner() {
+        let mut input = input();
+        input.owner = "octo-org".to_string();
+        input.repositories = "repo1".to_string();
+
+        let targets = InstallationTarget::resolve_all(&input).unwrap();
+
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].account(), "octo-org");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn groups_repositories_of_other_owners() {
+        let mut input = input();
+        input.repositories = "repo1, other-org/repo2\nOTHER-ORG/repo3, owner/repo4".to_string();
+        input.repository_ids = "42".to_string();
+
+        let targets = InstallationTarget::resolve_all(&input).unwrap();
+
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].account(), "owner");
+        assert_eq!(
+            targets[0].repository_names(),
+            Some(vec!["repo1".to_string(), "repo4".to_string()])
+        );
+        assert_eq!(targets[0].repository_ids(), Some(vec![42]));
+        assert_eq!(targets[1].account(), "other-org");
+        assert_eq!(
+            targets[1].repository_names(),
+            Some(vec!["repo2".to_string(), "repo3".to_string()])
+        );
+        assert_eq!(targets[1].repository_ids(), None);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn resolves_repositories_of_single_other_owner() {
+        let mut input = input();
+        input.repositories = "other-org/repo1".to_string();
+
+        let targets = InstallationTarget::resolve_all(&input).unwrap();
+
+        assert_eq!(targets.len(), 1);
+        assert_eq!(
+            targets[0].installation_paths(None),
+            vec!["/repos/other-org/repo1/installation".to_string()]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_repositories_of_multiple_owners_with_installation_id() {
+        let mut input = input();
+        input.repositories = "repo1, other-org/repo2".to_string();
+        input.installation_id = "42".to_string();
+
+        assert_eq!(
+            InstallationTarget::resolve_all(&input)
+                .err()
+                .unwrap()
+                .to_string(),
+            "repositories of multiple owners cannot be used with enterprise or installation-id"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_multiple_owners_with_repositories() {
+        let mut input = input();
+        input.owner = "org-a,org-b".to_string();
+        input.repositories = "repo1".to_string();
+
+        assert_eq!(
+            InstallationTarget::resolve_all(&input)
+                .err()
+                .unwrap()
+                .to_string(),
+            "multiple owners cannot be used with enterprise, installation-id, repositories, or repository-ids"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn resolves_installation_id_target() {
+        let mut input = input();
+        input.installation_id = " 42 ".to_string();
+        input.owner = "octo-org".to_string();
+        input.verify_installation = true;
+
+        let target = InstallationTarget::resolve(&input).unwrap();
+
+        assert_eq!(
+            target.installation_paths(None),
+            vec!["/app/installations/42".to_string()]
+        );
+        assert_eq!(target.repository_names(), None);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn scopes_installation_id_target_to_current_repository_by_default() {
+        let mut input = input();
+        input.installation_id = "42".to_string();
+
+        let target = InstallationTarget::resolve(&input).unwrap();
+        assert_eq!(target.repository_names(), Some(vec!["current".to_string()]));
+        assert_eq!(target.repository_ids(), None);
+
+        input.repo_id = "123456".to_string();
+        let target = InstallationTarget::resolve(&input).unwrap();
+        assert_eq!(target.repository_names(), Some(Vec::new()));
+        assert_eq!(target.repository_ids(), Some(vec![123456]));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn scopes_installation_id_target_to_repositories() {
+        let mut input = input();
+        input.installation_id = "42".to_string();
+        input.repositories = "repo1, owner/repo2".to_string();
+
+        let target = InstallationTarget::resolve(&input).unwrap();
+
+        assert_eq!(
+            target.repository_names(),
+            Some(vec!["repo1".to_string(), "repo2".to_string()])
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_invalid_installation_id() {
+        let mut input = input();
+        input.installation_id = "octo-org".to_string();
+
+        assert_eq!(
+            InstallationTarget::resolve(&input)
+                .err()
+                .unwrap()
+                .to_string(),
+            "installation-id must be a positive integer"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn resolves_auto_installation_target() {
+        let mut input = input();
+        input.installation = "auto".to_string();
+        input.owner = "octo-org".to_string();
+        input.installation_account_type = "organization".to_string();
+        input.repositories = "repo1, octo-org/repo2".to_string();
+
+        let targets = InstallationTarget::resolve_all(&input).unwrap();
+
+        assert_eq!(targets.len(), 1);
+        assert!(matches!(
+            &targets[0],
+            InstallationTarget::Auto {
+                account: Some(account),
+                account_type: Some(account_type),
+                ..
+            } if account == "octo-org" && account_type == "Organization"
+        ));
+        assert_eq!(
+            targets[0].repository_names(),
+            Some(vec!["repo1".to_string(), "repo2".to_string()])
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_auto_installation_with_installation_id() {
+        let mut input = input();
+        input.installation = "auto".to_string();
+        input.installation_id = "42".to_string();
+
+        assert_eq!(
+            InstallationTarget::resolve(&input)
+                .err()
+                .unwrap()
+                .to_string(),
+            "installation: auto cannot be used with installation-id or enterprise"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_unknown_installation_mode() {
+        let mut input = input();
+        input.installation = "first".to_string();
+
+        assert_eq!(
+            InstallationTarget::resolve_all(&input)
+                .err()
+                .unwrap()
+                .to_string(),
+            "installation must be 'auto' or empty, got 'first'"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn picks_single_installation() {
+        let installations = installations().into_iter().take(1).collect();
 
-    fn input() -> Input {
-        Input {
-            app_id: "legacy-app-id".to_string(),
-            client_id: "client-id".to_string(),
-            private_key: "private-key".to_string(),
-            github_api_url: "https://api.github.com".to_string(),
-            endpoint: String::new(),
-            owner: String::new(),
-            repositories: String::new(),
-            enterprise: String::new(),
-            skip_token_revoke: false,
-            repo: "owner/current".to_string(),
-            repo_owner: "owner".to_string(),
-        }
+        assert_eq!(pick_installation(installations, None, None).unwrap().id, 1);
     }
 
-    #[wasm_bindgen_test]
-    fn parses_default_github_api_url() {
-        let endpoint = ApiEndpoint::from_inputs("https://api.github.com", "").unwrap();
+    #[wasm_bindgen_test(unsupported = test)]
+    fn picks_installation_by_account_and_type() {
+        assert_eq!(
+            pick_installation(installations(), Some("OctoCat"), None)
+                .unwrap()
+                .id,
+            2
+        );
+        assert_eq!(
+            pick_installation(installations(), None, Some("Organization"))
+                .unwrap()
+                .id,
+            1
+        );
+        assert_eq!(
+            pick_installation(installations(), Some("octo-enterprise"), None)
+                .unwrap()
+                .id,
+            3
+        );
+        assert_eq!(
+            pick_installation(installations(), None, Some("Enterprise"))
+                .unwrap()
+                .id,
+            3
+        );
+    }
 
+    #[wasm_bindgen_test(unsupported = test)]
+    fn lists_candidates_of_ambiguous_installations() {
         assert_eq!(
-            endpoint.uri("/repos/owner/repo/installation").unwrap(),
-            "https://api.github.com/repos/owner/repo/installation"
+            pick_installation(installations(), None, None)
+                .err()
+                .unwrap()
+                .to_string(),
+            "3 installations of the GitHub App match; set owner or installation-account-type to pick one of octo-org (Organization, installation 1), octocat (User, installation 2), octo-enterprise (Enterprise, installation 3)"
         );
     }
 
-    #[wasm_bindgen_test]
-    fn preserves_github_enterprise_api_base_path() {
-        let endpoint = ApiEndpoint::from_inputs("https://github.example.com/api/v3/", "").unwrap();
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_installation_filters_matching_nothing() {
+        assert_eq!(
+            pick_installation(installations(), Some("octo-labs"), Some("User"))
+                .err()
+                .unwrap()
+                .to_string(),
+            "no installation of the GitHub App matches octo-labs User"
+        );
+        assert_eq!(
+            pick_installation(Vec::new(), None, None)
+                .err()
+                .unwrap()
+                .to_string(),
+            "the GitHub App has no installations"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn looks_up_pattern_only_targets_by_owner() {
+        let mut input = input();
+        input.owner = "octo-org".to_string();
+        input.repositories = "service-*".to_string();
+
+        let target = InstallationTarget::resolve(&input).unwrap();
 
         assert_eq!(
-            endpoint
-                .uri("/app/installations/123/access_tokens")
-                .unwrap(),
-            "https://github.example.com/api/v3/app/installations/123/access_tokens"
+            target.installation_paths(None),
+            vec![
+                "/orgs/octo-org/installation".to_string(),
+                "/users/octo-org/installation".to_string()
+            ]
+        );
+        assert_eq!(
+            target.repository_names(),
+            Some(vec!["service-*".to_string()])
         );
     }
 
-    #[wasm_bindgen_test]
-    fn endpoint_alias_overrides_github_api_url() {
-        let endpoint = ApiEndpoint::from_inputs(
-            "https://api.github.com",
-            "https://github.example.com/api/v3",
-        )
-        .unwrap();
+    #[wasm_bindgen_test(unsupported = test)]
+    fn matches_repository_globs() {
+        assert!(glob_match("service-*", "service-api"));
+        assert!(glob_match("service-*", "Service-"));
+        assert!(glob_match("*-api", "billing-api"));
+        assert!(glob_match("repo?", "repo1"));
+        assert!(glob_match("*a*b*", "xaxxbx"));
+        assert!(!glob_match("service-*", "services"));
+        assert!(!glob_match("repo?", "repo12"));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn expands_repository_patterns() {
+        let available = vec![
+            "service-api".to_string(),
+            "service-web".to_string(),
+            "docs".to_string(),
+        ];
+        let repositories = vec![
+            "docs".to_string(),
+            "service-*".to_string(),
+            "service-api".to_string(),
+        ];
 
         assert_eq!(
-            endpoint.uri("/installation/token").unwrap(),
-            "https://github.example.com/api/v3/installation/token"
+            expand_repositories(&repositories, &available).unwrap(),
+            vec!["docs", "service-api", "service-web"]
         );
     }
 
-    #[wasm_bindgen_test]
-    fn rejects_github_api_url_without_authority() {
-        assert!(ApiEndpoint::from_inputs("https:///api/v3", "").is_err());
+    #[wasm_bindgen_test(unsupported = test)]
+    fn expands_name_prefix_patterns() {
+        let available = vec![
+            "infra".to_string(),
+            "infra-terraform".to_string(),
+            "docs".to_string(),
+        ];
+
+        assert_eq!(
+            expand_repositories(&["infra*".to_string()], &available).unwrap(),
+            vec!["infra", "infra-terraform"]
+        );
     }
 
-    #[wasm_bindgen_test]
-    fn client_id_prefers_new_input_over_deprecated_app_id() {
+    #[wasm_bindgen_test(unsupported = test)]
+    fn selects_every_repository_of_other_owner() {
         let mut input = input();
-        input.app_id = "legacy-app-id".to_string();
-        input.client_id = "client-id".to_string();
+        input.repositories = "repo1, octo-labs/**".to_string();
 
-        assert_eq!(input.client_id().unwrap(), "client-id");
+        let targets = InstallationTarget::resolve_all(&input).unwrap();
+
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[1].account(), "octo-labs");
+        assert_eq!(targets[1].repository_names(), Some(vec!["**".to_string()]));
+        assert_eq!(
+            targets[1].installation_paths(None),
+            vec![
+                "/orgs/octo-labs/installation".to_string(),
+                "/users/octo-labs/installation".to_string()
+            ]
+        );
+        assert_eq!(
+            expand_repositories(
+                &["**".to_string()],
+                &["site".to_string(), "api".to_string()]
+            )
+            .unwrap(),
+            vec!["site", "api"]
+        );
     }
 
-    #[wasm_bindgen_test]
-    fn client_id_falls_back_to_deprecated_app_id() {
-        let mut input = input();
-        input.app_id = "legacy-app-id".to_string();
-        input.client_id = " ".to_string();
+    #[wasm_bindgen_test(unsupported = test)]
+    fn names_repositories_missing_from_installation() {
+        let available = vec![repository("docs", &[]), repository("api", &[])];
+        let repositories = vec![
+            "Docs".to_string(),
+            "web".to_string(),
+            "a*".to_string(),
+            "infra".to_string(),
+        ];
 
-        assert_eq!(input.client_id().unwrap(), "legacy-app-id");
+        assert_eq!(
+            select_repositories(&repositories, None, &available)
+                .unwrap_err()
+                .to_string(),
+            "the installation cannot access web, infra; check that they exist and that the GitHub App is installed on them"
+        );
     }
 
-    #[wasm_bindgen_test]
-    fn client_id_rejects_blank_aliases() {
+    #[wasm_bindgen_test(unsupported = test)]
+    fn validates_only_repositories_other_than_lookup_repository() {
         let mut input = input();
-        input.app_id = " ".to_string();
-        input.client_id = String::new();
+        input.owner = "octo-org".to_string();
+        input.repositories = "repo1".to_string();
+        let target = InstallationTarget::resolve(&input).unwrap();
+        assert!(!target.names_unverified_repositories());
+
+        input.repositories = "repo1, repo2".to_string();
+        let target = InstallationTarget::resolve(&input).unwrap();
+        assert!(target.names_unverified_repositories());
+
+        input.repositories = String::new();
+        input.owner = String::new();
+        input.repo_id = "123456".to_string();
+        let target = InstallationTarget::resolve(&input).unwrap();
+        assert!(!target.names_unverified_repositories());
+    }
 
+    #[wasm_bindgen_test(unsupported = test)]
+    fn logs_message_of_github_errors() {
         assert_eq!(
-            input.client_id().unwrap_err().to_string(),
-            "client-id or deprecated app-id must be set to a non-empty string"
+            error_message(br#"{"message":"There is at least one repository that does not exist or is not accessible to the parent installation.","documentation_url":"https://docs.github.com"}"#),
+            "There is at least one repository that does not exist or is not accessible to the parent installation."
         );
+        assert_eq!(error_message(b"Bad Gateway"), "Bad Gateway");
     }
 
-    #[wasm_bindgen_test]
-    fn private_key_normalizes_escaped_newlines() {
+    #[wasm_bindgen_test(unsupported = test)]
+    fn looks_up_installation_by_first_named_repository() {
         let mut input = input();
-        input.private_key = "-----BEGIN KEY-----\\nabc\\n-----END KEY-----".to_string();
+        input.owner = "octo-org".to_string();
+        input.repositories = "service-*, repo1, repo2".to_string();
+
+        let target = InstallationTarget::resolve(&input).unwrap();
+
+        assert_eq!(target.lookup_repository(), Some("repo1"));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_patterns_matching_nothing() {
+        let available = vec!["docs".to_string()];
 
         assert_eq!(
-            input.private_key().unwrap(),
-            "-----BEGIN KEY-----\nabc\n-----END KEY-----"
+            expand_repositories(&["service-*".to_string()], &available)
+                .unwrap_err()
+                .to_string(),
+            "repository pattern 'service-*' matched no repositories of the installation"
         );
     }
 
-    #[wasm_bindgen_test]
-    fn private_key_rejects_blank_value() {
+    #[wasm_bindgen_test(unsupported = test)]
+    fn parses_repository_topics() {
         let mut input = input();
-        input.private_key = " ".to_string();
+        input.repository_topics = "Deploy-Target,\nrust".to_string();
+        input.repository_topics_match = "any".to_string();
 
         assert_eq!(
-            input.private_key().unwrap_err().to_string(),
-            "private-key must be set to a non-empty string"
+            input.repository_query().unwrap(),
+            Some(RepositoryQuery {
+                topics: vec!["deploy-target".to_string(), "rust".to_string()],
+                match_all: false,
+                properties: Vec::new(),
+            })
         );
     }
 
-    #[wasm_bindgen_test]
-    fn resolves_current_repository_target_by_default() {
-        let target = InstallationTarget::resolve(&input()).unwrap();
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_unknown_topic_match_mode() {
+        let mut input = input();
+        input.repository_topics = "rust".to_string();
+        input.repository_topics_match = "some".to_string();
 
         assert_eq!(
-            target.installation_paths(),
-            vec!["/repos/owner/current/installation".to_string()]
+            input.repository_query().unwrap_err().to_string(),
+            "repository-topics-match must be 'all' or 'any', got 'some'"
         );
-        assert_eq!(target.repository_names(), Some(vec!["current".to_string()]));
     }
 
-    #[wasm_bindgen_test]
-    fn resolves_owner_target_without_repository_scope() {
+    #[wasm_bindgen_test(unsupported = test)]
+    fn resolves_topic_selection_against_repository_owner() {
         let mut input = input();
-        input.owner = "octo-org".to_string();
+        input.repository_topics = "deploy-target".to_string();
 
         let target = InstallationTarget::resolve(&input).unwrap();
 
         assert_eq!(
-            target.installation_paths(),
+            target.installation_paths(None),
             vec![
-                "/orgs/octo-org/installation".to_string(),
-                "/users/octo-org/installation".to_string()
+                "/orgs/owner/installation".to_string(),
+                "/users/owner/installation".to_string()
             ]
         );
-        assert_eq!(target.repository_names(), None);
+        assert_eq!(target.repository_names(), Some(Vec::new()));
     }
 
-    #[wasm_bindgen_test]
-    fn resolves_repository_list_against_owner() {
+    #[wasm_bindgen_test(unsupported = test)]
+    fn selects_repositories_by_all_or_any_topic() {
+        let available = vec![
+            repository("api", &["deploy-target", "rust"]),
+            repository("web", &["deploy-target"]),
+            repository("docs", &[]),
+        ];
+        let mut query = RepositoryQuery {
+            topics: vec!["deploy-target".to_string(), "rust".to_string()],
+            match_all: true,
+            properties: Vec::new(),
+        };
+
+        assert_eq!(
+            select_repositories(&[], Some(&query), &available).unwrap(),
+            vec!["api"]
+        );
+        query.match_all = false;
+        assert_eq!(
+            select_repositories(&["docs".to_string()], Some(&query), &available).unwrap(),
+            vec!["docs", "api", "web"]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_topics_matching_nothing() {
+        let query = RepositoryQuery {
+            topics: vec!["deploy-target".to_string()],
+            match_all: true,
+            properties: Vec::new(),
+        };
+
+        assert_eq!(
+            select_repositories(&[], Some(&query), &[repository("docs", &[])])
+                .unwrap_err()
+                .to_string(),
+            "no repositories of the installation have topics deploy-target"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn parses_repository_properties() {
         let mut input = input();
-        input.owner = "octo-org".to_string();
-        input.repositories = "repo1, octo-org/repo2\nrepo3".to_string();
+        input.repository_properties = "team=payments, tier = prod".to_string();
 
-        let target = InstallationTarget::resolve(&input).unwrap();
+        assert_eq!(
+            input.repository_query().unwrap(),
+            Some(RepositoryQuery {
+                topics: Vec::new(),
+                match_all: true,
+                properties: vec![
+                    ("team".to_string(), "payments".to_string()),
+                    ("tier".to_string(), "prod".to_string())
+                ],
+            })
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_repository_properties_without_value() {
+        let mut input = input();
+        input.repository_properties = "team".to_string();
 
         assert_eq!(
-            target.installation_paths(),
-            vec!["/repos/octo-org/repo1/installation".to_string()]
+            input.repository_query().unwrap_err().to_string(),
+            "invalid repository property 'team', expected 'name=value'"
         );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn selects_repositories_by_custom_properties() {
+        let mut available = vec![
+            repository("payments-api", &[]),
+            repository("payments-staging", &[]),
+            repository("docs", &[]),
+        ];
+        let property_values: Vec<RepositoryPropertyValues> =
+            serde_json::from_value(serde_json::json!([
+                {
+                    "repository_id": 1,
+                    "repository_name": "payments-api",
+                    "repository_full_name": "octo-org/payments-api",
+                    "properties": [
+                        { "property_name": "team", "value": "payments" },
+                        { "property_name": "tier", "value": ["prod", "edge"] }
+                    ]
+                },
+                {
+                    "repository_id": 2,
+                    "repository_name": "payments-staging",
+                    "repository_full_name": "octo-org/payments-staging",
+                    "properties": [
+                        { "property_name": "team", "value": "payments" },
+                        { "property_name": "tier", "value": null }
+                    ]
+                }
+            ]))
+            .unwrap();
+        attach_property_values(&mut available, property_values);
+        let query = RepositoryQuery {
+            topics: Vec::new(),
+            match_all: true,
+            properties: vec![
+                ("team".to_string(), "payments".to_string()),
+                ("tier".to_string(), "prod".to_string()),
+            ],
+        };
+
         assert_eq!(
-            target.repository_names(),
-            Some(vec![
-                "repo1".to_string(),
-                "repo2".to_string(),
-                "repo3".to_string()
-            ])
+            select_repositories(&[], Some(&query), &available).unwrap(),
+            vec!["payments-api"]
         );
+        assert_eq!(query.to_string(), "properties team=payments, tier=prod");
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
+    fn deserializes_installation_repositories() {
+        let response: InstallationRepositoriesResponse =
+            serde_json::from_value(serde_json::json!({
+                "total_count": 1,
+                "repositories": [{ "id": 1, "name": "docs", "full_name": "octo-org/docs" }]
+            }))
+            .unwrap();
+
+        assert_eq!(response.total_count, 1);
+        assert_eq!(response.repositories[0].name, "docs");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
     fn rejects_enterprise_with_repository_scope() {
         let mut input = input();
         input.enterprise = "octo-enterprise".to_string();
@@ -754,7 +3944,7 @@ mod tests {
         assert!(InstallationTarget::resolve(&input).is_err());
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn rejects_repository_owner_mismatch() {
         let error = parse_repository("octo-org", "other-org/repo").unwrap_err();
 
@@ -764,7 +3954,7 @@ mod tests {
         );
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn normalizes_permission_inputs_from_environment_names() {
         let permissions = permissions_from_vars([
             (
@@ -786,7 +3976,7 @@ mod tests {
         assert!(!permissions.contains_key("owner"));
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn omits_permissions_when_no_permission_inputs_are_set() {
         assert_eq!(
             permissions_from_vars([
@@ -797,13 +3987,14 @@ mod tests {
         );
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn serializes_access_token_request_with_permissions() {
         let mut permissions = BTreeMap::new();
         permissions.insert("contents".to_string(), "read".to_string());
         permissions.insert("pull_requests".to_string(), "write".to_string());
         let body = AccessTokenRequest {
             repositories: Some(vec!["repo".to_string()]),
+            repository_ids: None,
             permissions: Some(permissions),
         };
 
@@ -819,7 +4010,7 @@ mod tests {
         );
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn deserializes_installation_metadata_response() {
         let installation: InstallationResponse = serde_json::from_value(serde_json::json!({
             "id": 123,
@@ -829,24 +4020,48 @@ mod tests {
 
         assert_eq!(installation.id, 123);
         assert_eq!(installation.app_slug, "octo-app");
+        assert!(installation.account.is_none());
+        assert!(check_suspended(&installation).is_ok());
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_suspended_installation() {
+        let installation: InstallationResponse = serde_json::from_value(serde_json::json!({
+            "id": 123,
+            "app_slug": "octo-app",
+            "account": {"login": "octo-org", "type": "Organization"},
+            "target_type": "Organization",
+            "repository_selection": "selected",
+            "suspended_at": "2024-01-01T00:00:00Z",
+            "suspended_by": {"login": "octocat", "type": "User"}
+        }))
+        .unwrap();
+
+        assert_eq!(installation.target_type, "Organization");
+        assert_eq!(installation.repository_selection, "selected");
+        assert_eq!(
+            check_suspended(&installation).err().unwrap().to_string(),
+            "installation 123 on octo-org was suspended by octocat at 2024-01-01T00:00:00Z; ask an owner of the account to unsuspend the GitHub App"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
     fn output_state_round_trips_installation_metadata() {
-        let output = Output {
-            token: "ghs_token".to_string(),
-            installation_id: "123".to_string(),
-            app_slug: "octo-app".to_string(),
-            expires_at: "2999-01-01T00:00:00Z".to_string(),
-        };
-        let value = serde_json::to_value(&output).unwrap();
+        let value = serde_json::to_value(output()).unwrap();
 
         assert_eq!(
             value,
             serde_json::json!({
-                "token": "ghs_token",
+                "token": "ghs_a",
+                "tokens": "{\"octo-org\":\"ghs_a\"}",
+                "token_batches": "",
+                "repository_tokens": "{\"octo-org/repo\":\"ghs_a\"}",
                 "installation_id": "123",
                 "app_slug": "octo-app",
+                "target_type": "Organization",
+                "account_login": "octo-org",
+                "key_fingerprint": "SHA256:fingerprint",
+                "jwt": "",
                 "expires_at": "2999-01-01T00:00:00Z"
             })
         );
@@ -857,7 +4072,99 @@ mod tests {
         assert_eq!(round_trip.expires_at, "2999-01-01T00:00:00Z");
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
+    fn revokes_every_issued_token() {
+        let output = Output {
+            tokens: r#"{"org-a":"ghs_a","org-b":"ghs_b"}"#.to_string(),
+            ..output()
+        };
+
+        assert_eq!(
+            output.issued_tokens(),
+            vec![
+                ("ghs_a".to_string(), "2999-01-01T00:00:00Z".to_string()),
+                ("ghs_b".to_string(), "2999-01-01T00:00:00Z".to_string())
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn falls_back_to_token_without_tokens_output() {
+        let output = Output {
+            tokens: String::new(),
+            ..output()
+        };
+
+        assert_eq!(
+            output.issued_tokens(),
+            vec![("ghs_a".to_string(), "2999-01-01T00:00:00Z".to_string())]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn revokes_every_token_batch() {
+        let output = Output {
+            token_batches: serde_json::json!([
+                {"account": "octo-org", "token": "ghs_a", "repositories": ["octo-org/a"], "expires_at": "2999-01-01T00:00:00Z"},
+                {"account": "octo-org", "token": "ghs_b", "repositories": ["octo-org/b"], "expires_at": "2999-01-01T00:05:00Z"},
+            ])
+            .to_string(),
+            ..output()
+        };
+
+        assert_eq!(
+            output.issued_tokens(),
+            vec![
+                ("ghs_a".to_string(), "2999-01-01T00:00:00Z".to_string()),
+                ("ghs_b".to_string(), "2999-01-01T00:05:00Z".to_string())
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn keeps_selections_within_limit_in_one_batch() {
+        let repositories = Some(vec!["repo".to_string()]);
+
+        assert_eq!(
+            batch_repositories(repositories.clone(), Some(vec![1])),
+            vec![(repositories, Some(vec![1]))]
+        );
+        assert_eq!(batch_repositories(None, None), vec![(None, None)]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn splits_selections_beyond_limit_into_batches() {
+        let repositories = (0..1001).map(|i| format!("repo{i}")).collect::<Vec<_>>();
+
+        let batches = batch_repositories(Some(repositories), Some(vec![1, 2]));
+
+        assert_eq!(
+            batches
+                .iter()
+                .map(|(names, ids)| (
+                    names.as_ref().map_or(0, Vec::len),
+                    ids.as_ref().map_or(0, Vec::len)
+                ))
+                .collect::<Vec<_>>(),
+            vec![(500, 0), (500, 0), (1, 2)]
+        );
+        assert_eq!(batches[2].0, Some(vec!["repo1000".to_string()]));
+        assert_eq!(batches[2].1, Some(vec![1, 2]));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn counts_duplicate_repositories_once() {
+        let mut repositories = (0..500).map(|i| format!("repo{i}")).collect::<Vec<_>>();
+        repositories.push("REPO0".to_string());
+
+        let batches = batch_repositories(Some(repositories), Some(vec![1, 1]));
+
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].0.as_ref().map(Vec::len), Some(500));
+        assert_eq!(batches[1], (Some(Vec::new()), Some(vec![1])));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
     fn deserializes_access_token_expiration() {
         let response: AccessTokenResponse = serde_json::from_value(serde_json::json!({
             "token": "ghs_token",
@@ -869,14 +4176,26 @@ mod tests {
         assert_eq!(response.expires_at, "2999-01-01T00:00:00Z");
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
+    fn deserializes_repositories_covered_by_token() {
+        let response: AccessTokenResponse = serde_json::from_value(serde_json::json!({
+            "token": "ghs_token",
+            "expires_at": "2999-01-01T00:00:00Z",
+            "repositories": [{"name": "repo", "full_name": "octo-org/repo"}]
+        }))
+        .unwrap();
+
+        assert_eq!(response.repositories[0].full_name, "octo-org/repo");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
     fn detects_expired_tokens() {
         assert!(token_expired("2000-01-01T00:00:00Z"));
         assert!(!token_expired("2999-01-01T00:00:00Z"));
         assert!(!token_expired("not-a-timestamp"));
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn formats_installation_token_authorization_header() {
         assert_eq!(
             access_token_authorization_header("ghs_token"),
@@ -884,7 +4203,26 @@ mod tests {
         );
     }
 
+    #[wasm_bindgen_test(unsupported = test)]
+    fn strips_bearer_prefix_from_jwt() {
+        assert_eq!(bare_jwt("Bearer header.payload.sig"), "header.payload.sig");
+    }
+
+    #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
+    async fn skips_revocation_without_token() {
+        let request = RemoveAccessTokenRequest {
+            endpoint: ApiEndpoint::parse("https://api.github.com").unwrap(),
+            token: String::new(),
+            expires_at: String::new(),
+            skip_token_revoke: false,
+            client: reqwest::Client::new(),
+        };
+
+        assert!(request.execute().await.is_ok());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
     fn action_metadata_includes_generated_permission_inputs() {
         let action = include_str!("../action.yaml");
 
@@ -901,7 +4239,7 @@ mod tests {
             .contains("description: GitHub App permission level to grant to the access token"));
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn action_metadata_keeps_declared_outputs_after_permission_inputs() {
         let action = include_str!("../action.yaml");
         let permissions = action.find("  permission-actions:").unwrap();
@@ -1,32 +1,145 @@
-use pem::Pem;
+use base64ct::{Base64, Base64UrlUnpadded, Encoding};
+use log::warn;
+use pem::{EncodeConfig, LineEnding, Pem};
+use pkcs8::{EncryptedPrivateKeyInfo, PrivateKeyInfo};
 use rsa::pkcs1::DecodeRsaPrivateKey;
-use serde::Serialize;
-use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
 use wasm_actions::prelude::Error;
-use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+use wasm_bindgen::JsValue;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
     js_sys::{ArrayBuffer, Object, Uint8Array},
-    Crypto, CryptoKey,
+    CryptoKey, SubtleCrypto,
 };
 
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(thread_local_v2, js_name = crypto)]
-    static CRYPTO: Crypto;
-}
-
 #[derive(Serialize)]
 struct RsaHashedImportParams {
     name: String,
     hash: String,
 }
 
-fn load_pkey(data: &str) -> Result<Vec<u8>, Error> {
+/// The encoding a private key was supplied in, detected by [`normalize_pkey`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFormat {
+    Pem,
+    SingleLinePem,
+    Base64Pem,
+    Base64Pkcs1Der,
+    Base64Pkcs8Der,
+    Base64EncryptedPkcs8Der,
+    Jwk,
+    Unknown,
+}
+
+impl fmt::Display for KeyFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pem => "PEM",
+            Self::SingleLinePem => "single-line PEM",
+            Self::Base64Pem => "base64-encoded PEM",
+            Self::Base64Pkcs1Der => "base64-encoded PKCS#1 DER",
+            Self::Base64Pkcs8Der => "base64-encoded PKCS#8 DER",
+            Self::Base64EncryptedPkcs8Der => "base64-encoded encrypted PKCS#8 DER",
+            Self::Jwk => "JSON Web Key",
+            Self::Unknown => "unrecognized format",
+        })
+    }
+}
+
+/// Rewrites commonly mangled private keys into PEM.
+///
+/// Accepts PEM whose line breaks were replaced by spaces, base64 of a PEM file,
+/// and base64 of a PKCS#1 or PKCS#8 DER key. JSON Web Keys and anything else
+/// are returned as is.
+pub fn normalize_pkey(data: &str) -> (String, KeyFormat) {
+    let data = data.trim();
+    if data.starts_with('{') {
+        return (data.to_string(), KeyFormat::Jwk);
+    }
+    if data.contains("-----BEGIN ") {
+        if data.contains('\n') {
+            return (data.to_string(), KeyFormat::Pem);
+        }
+        return match Pem::from_str(data) {
+            Ok(pem) => (encode_pem(&pem), KeyFormat::SingleLinePem),
+            Err(_) => (data.to_string(), KeyFormat::SingleLinePem),
+        };
+    }
+
+    let Ok(decoded) = Base64::decode_vec(&data.split_whitespace().collect::<String>()) else {
+        return (data.to_string(), KeyFormat::Unknown);
+    };
+    if let Ok(text) = std::str::from_utf8(&decoded) {
+        if text.contains("-----BEGIN ") {
+            let (pem, _) = normalize_pkey(text);
+            return (pem, KeyFormat::Base64Pem);
+        }
+    }
+
+    let (tag, format) = if PrivateKeyInfo::try_from(decoded.as_slice()).is_ok() {
+        ("PRIVATE KEY", KeyFormat::Base64Pkcs8Der)
+    } else if rsa::pkcs1::RsaPrivateKey::try_from(decoded.as_slice()).is_ok() {
+        ("RSA PRIVATE KEY", KeyFormat::Base64Pkcs1Der)
+    } else if EncryptedPrivateKeyInfo::try_from(decoded.as_slice()).is_ok() {
+        ("ENCRYPTED PRIVATE KEY", KeyFormat::Base64EncryptedPkcs8Der)
+    } else {
+        return (data.to_string(), KeyFormat::Unknown);
+    };
+    (encode_pem(&Pem::new(tag, decoded)), format)
+}
+
+/// Splits concatenated PEM blocks so that each key can be tried in turn.
+///
+/// Input with zero or one PEM block is returned as a single item.
+pub fn split_pkeys(data: &str) -> Vec<&str> {
+    const BEGIN: &str = "-----BEGIN ";
+    const END: &str = "-----END ";
+    const DASHES: &str = "-----";
+
+    let mut blocks = Vec::new();
+    let mut rest = data;
+    while let Some(begin) = rest.find(BEGIN) {
+        let block = &rest[begin..];
+        let Some(end) = block.find(END) else {
+            break;
+        };
+        let Some(close) = block[end + END.len()..].find(DASHES) else {
+            break;
+        };
+        let len = end + END.len() + close + DASHES.len();
+        blocks.push(&block[..len]);
+        rest = &block[len..];
+    }
+
+    if blocks.len() > 1 {
+        blocks
+    } else {
+        vec![data]
+    }
+}
+
+fn encode_pem(pem: &Pem) -> String {
+    pem::encode_config(pem, EncodeConfig::new().set_line_ending(LineEnding::LF))
+}
+
+/// Parses a PEM or JWK private key into PKCS#8 DER, decrypting it with `passphrase` if needed.
+pub fn load_pkey(data: &str, passphrase: Option<&str>) -> Result<Vec<u8>, Error> {
+    if data.trim_start().starts_with('{') {
+        if passphrase.is_some() {
+            warn!("private-key-passphrase is ignored because the private key is not encrypted");
+        }
+        return jwk_to_pkcs8(data);
+    }
+
     let pem = Pem::from_str(data).map_err(|e| Error::from(format!("failed to parse PEM: {e}")))?;
+    if passphrase.is_some() && pem.tag() != "ENCRYPTED PRIVATE KEY" {
+        warn!("private-key-passphrase is ignored because the private key is not encrypted");
+    }
     match pem.tag() {
         "PRIVATE KEY" => Ok(pem.into_contents()),
         "RSA PRIVATE KEY" => pkcs1_to_pkcs8(data),
+        "ENCRYPTED PRIVATE KEY" => decrypt_pkcs8(pem.contents(), passphrase),
         _ => Err(Error::from(format!(
             "unsupported key format: {}",
             pem.tag()
@@ -43,11 +156,280 @@ fn pkcs1_to_pkcs8(data: &str) -> Result<Vec<u8>, Error> {
     Ok(Pem::from_str(&pkcs8).map_err(Error::new)?.into_contents())
 }
 
-pub async fn sign_sha256(buf: &[u8], pkey: &str) -> Result<Vec<u8>, Error> {
-    let crypto = CRYPTO.with(|o| o.clone());
-    let crypto = crypto.subtle();
-    let pkey = load_pkey(pkey)?;
-    let pkey = Uint8Array::from(pkey.as_slice()).buffer();
+/// The members of an RSA private JSON Web Key (RFC 7518 section 6.3) needed to rebuild it.
+#[derive(Deserialize)]
+struct Jwk {
+    kty: String,
+    n: Option<String>,
+    e: Option<String>,
+    d: Option<String>,
+    p: Option<String>,
+    q: Option<String>,
+}
+
+fn jwk_to_pkcs8(data: &str) -> Result<Vec<u8>, Error> {
+    use rsa::pkcs8::EncodePrivateKey;
+
+    let jwk: Jwk =
+        serde_json::from_str(data).map_err(|e| Error::from(format!("failed to parse JWK: {e}")))?;
+    if jwk.kty != "RSA" {
+        return Err(Error::from(format!(
+            "unsupported JWK key type '{}', GitHub Apps sign with RS256 and need an RSA key",
+            jwk.kty
+        )));
+    }
+
+    let member = |name: &str, value: Option<String>| {
+        let value =
+            value.ok_or_else(|| Error::from(format!("JWK is missing the '{name}' member")))?;
+        Base64UrlUnpadded::decode_vec(&value)
+            .map(|bytes| rsa::BigUint::from_bytes_be(&bytes))
+            .map_err(|e| Error::from(format!("JWK member '{name}' is not base64url: {e}")))
+    };
+    let n = member("n", jwk.n)?;
+    let e = member("e", jwk.e)?;
+    let d = member("d", jwk.d)?;
+    // without the primes, the rsa crate recovers them from n, e and d
+    let primes = match (jwk.p, jwk.q) {
+        (Some(p), Some(q)) => vec![member("p", Some(p))?, member("q", Some(q))?],
+        _ => Vec::new(),
+    };
+
+    // from_components validates the key
+    let pkey = rsa::RsaPrivateKey::from_components(n, e, d, primes)
+        .map_err(|e| Error::from(format!("invalid RSA JWK: {e}")))?;
+    Ok(pkey.to_pkcs8_der().map_err(Error::new)?.as_bytes().to_vec())
+}
+
+fn decrypt_pkcs8(data: &[u8], passphrase: Option<&str>) -> Result<Vec<u8>, Error> {
+    let passphrase = passphrase.ok_or_else(|| {
+        Error::from("private-key-passphrase must be set to decrypt an encrypted private key")
+    })?;
+    let info = EncryptedPrivateKeyInfo::try_from(data)
+        .map_err(|e| Error::from(format!("failed to parse encrypted private key: {e}")))?;
+    let document = info.decrypt(passphrase).map_err(|e| {
+        Error::from(format!(
+            "failed to decrypt private key, check private-key-passphrase: {e}"
+        ))
+    })?;
+    Ok(document.as_bytes().to_vec())
+}
+
+/// A reason a private key cannot sign GitHub App JWTs, found by [`check_pem`] or [`check_pkcs8`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyProblem {
+    MultipleBlocks(usize),
+    StrayText,
+    Malformed,
+    PublicKey,
+    Certificate,
+    NotRsa(String),
+    WeakModulus(usize),
+    WeakExponent(u64),
+}
+
+impl KeyProblem {
+    /// One-line remediation for the problem.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Self::MultipleBlocks(_) => "keep only the private key block, or separate keys for rotation into complete PEM blocks",
+            Self::StrayText => "remove everything outside the -----BEGIN/-----END lines, e.g. the Bag Attributes printed by openssl",
+            Self::Malformed => "the key looks truncated or corrupted; copy the whole .pem file again",
+            Self::PublicKey | Self::Certificate => "use the .pem file downloaded from the GitHub App settings, which holds the private key",
+            Self::NotRsa(_) => "GitHub Apps sign JWTs with RS256; generate a new private key in the GitHub App settings",
+            Self::WeakModulus(_) | Self::WeakExponent(_) => "GitHub generates 2048-bit keys with exponent 65537; generate a new private key in the GitHub App settings",
+        }
+    }
+}
+
+impl fmt::Display for KeyProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MultipleBlocks(n) => write!(f, "contains {n} PEM blocks"),
+            Self::StrayText => f.write_str("has text outside the PEM block"),
+            Self::Malformed => f.write_str("is not a valid PKCS#8 private key"),
+            Self::PublicKey => f.write_str("is a public key, not a private key"),
+            Self::Certificate => f.write_str("is a certificate, not a private key"),
+            Self::NotRsa(algorithm) => write!(f, "uses algorithm {algorithm}, not RSA"),
+            Self::WeakModulus(bits) => write!(
+                f,
+                "has a {bits}-bit modulus, at least 2048 bits are required"
+            ),
+            Self::WeakExponent(e) => {
+                write!(
+                    f,
+                    "has public exponent {e}, which must be odd and greater than 3"
+                )
+            }
+        }
+    }
+}
+
+/// Size and public exponent of an RSA key, reported by [`check_pkcs8`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RsaKeyInfo {
+    pub bits: usize,
+    pub exponent: u64,
+}
+
+impl RsaKeyInfo {
+    /// The public exponent of keys generated by GitHub.
+    pub const GITHUB_EXPONENT: u64 = 65537;
+}
+
+impl fmt::Display for RsaKeyInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RSA {}-bit key, public exponent {}",
+            self.bits, self.exponent
+        )
+    }
+}
+
+/// Checks the structure of a PEM key before [`load_pkey`] parses it.
+///
+/// Input that is not PEM, such as a JSON Web Key, is left to [`load_pkey`].
+pub fn check_pem(data: &str) -> Result<(), KeyProblem> {
+    const BEGIN: &str = "-----BEGIN ";
+    const END: &str = "-----END ";
+
+    let data = data.trim();
+    let blocks = data.matches(BEGIN).count();
+    if blocks == 0 {
+        return Ok(());
+    }
+    if blocks > 1 {
+        return Err(KeyProblem::MultipleBlocks(blocks));
+    }
+    let stray_text = match data.find(END) {
+        Some(end) => data[end..].lines().count() > 1 || !data.starts_with(BEGIN),
+        None => !data.starts_with(BEGIN),
+    };
+    if stray_text {
+        return Err(KeyProblem::StrayText);
+    }
+
+    let tag = data[BEGIN.len()..]
+        .split("-----")
+        .next()
+        .unwrap_or_default();
+    match tag {
+        "PUBLIC KEY" | "RSA PUBLIC KEY" => Err(KeyProblem::PublicKey),
+        "CERTIFICATE" | "TRUSTED CERTIFICATE" => Err(KeyProblem::Certificate),
+        "EC PRIVATE KEY" => Err(KeyProblem::NotRsa("EC".to_string())),
+        "DSA PRIVATE KEY" => Err(KeyProblem::NotRsa("DSA".to_string())),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a PKCS#8 DER key from [`load_pkey`] is an RSA key strong enough for GitHub.
+pub fn check_pkcs8(pkey: &[u8]) -> Result<RsaKeyInfo, KeyProblem> {
+    use pkcs8::ObjectIdentifier;
+
+    const RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+    const EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+    const ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+    const ED448: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.113");
+
+    let info = PrivateKeyInfo::try_from(pkey).map_err(|_| KeyProblem::Malformed)?;
+    let algorithm = info.algorithm.oid;
+    if algorithm != RSA_ENCRYPTION {
+        return Err(KeyProblem::NotRsa(match algorithm {
+            EC_PUBLIC_KEY => "EC".to_string(),
+            ED25519 => "Ed25519".to_string(),
+            ED448 => "Ed448".to_string(),
+            _ => algorithm.to_string(),
+        }));
+    }
+
+    let key =
+        rsa::pkcs1::RsaPrivateKey::try_from(info.private_key).map_err(|_| KeyProblem::Malformed)?;
+    let modulus = key.modulus.as_bytes();
+    let bits = match modulus.first() {
+        Some(first) => modulus.len() * 8 - first.leading_zeros() as usize,
+        None => 0,
+    };
+    let exponent = key
+        .public_exponent
+        .as_bytes()
+        .iter()
+        .try_fold(0u64, |e, byte| {
+            e.checked_mul(256).map(|e| e | u64::from(*byte))
+        })
+        .unwrap_or(u64::MAX);
+
+    if bits < 2048 {
+        Err(KeyProblem::WeakModulus(bits))
+    } else if weak_exponent(exponent) {
+        Err(KeyProblem::WeakExponent(exponent))
+    } else {
+        Ok(RsaKeyInfo { bits, exponent })
+    }
+}
+
+/// Exponents that are even cannot form an RSA key, and those of 3 or less are open to known attacks.
+fn weak_exponent(exponent: u64) -> bool {
+    exponent <= 3 || exponent.is_multiple_of(2)
+}
+
+/// Computes the key fingerprint shown in the GitHub App settings,
+/// i.e. `SHA256:` followed by the base64 SHA-256 digest of the DER public key.
+pub fn fingerprint_sha256(pkey: &[u8]) -> Result<String, Error> {
+    use rsa::{
+        pkcs8::{DecodePrivateKey, EncodePublicKey},
+        sha2::{Digest, Sha256},
+    };
+
+    let public_key = rsa::RsaPrivateKey::from_pkcs8_der(pkey)
+        .map_err(Error::new)?
+        .to_public_key()
+        .to_public_key_der()
+        .map_err(Error::new)?;
+    Ok(format!(
+        "SHA256:{}",
+        Base64::encode_string(&Sha256::digest(public_key.as_bytes()))
+    ))
+}
+
+/// Signs `buf` with RSASSA-PKCS1-v1_5 and SHA-256, using a PKCS#8 DER key from [`load_pkey`].
+///
+/// WebCrypto is used when `globalThis.crypto.subtle` is available,
+/// otherwise the pure-Rust implementation from the `rsa` crate is used.
+pub async fn sign_sha256(buf: &[u8], pkey: &[u8]) -> Result<Vec<u8>, Error> {
+    match subtle_crypto() {
+        Some(crypto) => webcrypto_sign_sha256(&crypto, buf, pkey).await,
+        None => rsa_sign_sha256(buf, pkey),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn subtle_crypto() -> Option<SubtleCrypto> {
+    use wasm_bindgen::JsCast;
+    use web_sys::js_sys::{global, Reflect};
+
+    let crypto = Reflect::get(&global(), &JsValue::from_str("crypto")).ok()?;
+    if crypto.is_undefined() || crypto.is_null() {
+        return None;
+    }
+    let subtle = Reflect::get(&crypto, &JsValue::from_str("subtle")).ok()?;
+    if subtle.is_undefined() || subtle.is_null() {
+        return None;
+    }
+    Some(subtle.unchecked_into())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn subtle_crypto() -> Option<SubtleCrypto> {
+    None
+}
+
+async fn webcrypto_sign_sha256(
+    crypto: &SubtleCrypto,
+    buf: &[u8],
+    pkey: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let pkey = Uint8Array::from(pkey).buffer();
     let params = RsaHashedImportParams {
         name: String::from("RSASSA-PKCS1-v1_5"),
         hash: String::from("SHA-256"),
@@ -72,3 +454,302 @@ pub async fn sign_sha256(buf: &[u8], pkey: &str) -> Result<Vec<u8>, Error> {
 
     Ok(sign.to_vec())
 }
+
+fn rsa_sign_sha256(buf: &[u8], pkey: &[u8]) -> Result<Vec<u8>, Error> {
+    use rsa::{
+        pkcs1v15::SigningKey,
+        pkcs8::DecodePrivateKey,
+        sha2::Sha256,
+        signature::{SignatureEncoding, Signer},
+    };
+
+    let pkey = rsa::RsaPrivateKey::from_pkcs8_der(pkey).map_err(Error::new)?;
+    let signing_key = SigningKey::<Sha256>::new(pkey);
+    Ok(signing_key.sign(buf).to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsa::{
+        pkcs1v15::{Signature, VerifyingKey},
+        pkcs8::DecodePrivateKey,
+        sha2::Sha256,
+        signature::Verifier,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    const PKCS8_PEM: &str = include_str!("testdata/private-key.pem");
+    const PKCS1_PEM: &str = include_str!("testdata/rsa-private-key.pem");
+    const ENCRYPTED_PEM: &str = include_str!("testdata/encrypted-private-key.pem");
+    const JWK: &str = include_str!("testdata/private-key.jwk.json");
+    const SCRYPT_ENCRYPTED_PEM: &str = include_str!("testdata/scrypt-encrypted-private-key.pem");
+    const WEAK_PKCS1_PEM: &str = include_str!("testdata/weak-rsa-private-key.pem");
+    const EC_PEM: &str = include_str!("testdata/ec-private-key.pem");
+    const ED25519_PEM: &str = include_str!("testdata/ed25519-private-key.pem");
+    const PUBLIC_KEY_PEM: &str = include_str!("testdata/public-key.pem");
+    const CERTIFICATE_PEM: &str = include_str!("testdata/certificate.pem");
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rsa_backend_signature_verifies_with_public_key() {
+        let pkey = load_pkey(PKCS8_PEM, None).unwrap();
+        let sig = rsa_sign_sha256(b"header.payload", &pkey).unwrap();
+
+        let public_key = rsa::RsaPrivateKey::from_pkcs8_der(&pkey)
+            .unwrap()
+            .to_public_key();
+        let sig = Signature::try_from(sig.as_slice()).unwrap();
+        assert!(VerifyingKey::<Sha256>::new(public_key)
+            .verify(b"header.payload", &sig)
+            .is_ok());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rsa_backend_accepts_pkcs1_and_pkcs8_keys() {
+        let pkcs8 =
+            rsa_sign_sha256(b"header.payload", &load_pkey(PKCS8_PEM, None).unwrap()).unwrap();
+        let pkcs1 =
+            rsa_sign_sha256(b"header.payload", &load_pkey(PKCS1_PEM, None).unwrap()).unwrap();
+
+        assert_eq!(pkcs8, pkcs1);
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn webcrypto_and_rsa_backends_produce_same_signature() {
+        let pkey = load_pkey(PKCS8_PEM, None).unwrap();
+        let crypto = subtle_crypto().unwrap();
+
+        assert_eq!(
+            webcrypto_sign_sha256(&crypto, b"header.payload", &pkey)
+                .await
+                .unwrap(),
+            rsa_sign_sha256(b"header.payload", &pkey).unwrap()
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn keeps_multiline_pem_as_is() {
+        assert_eq!(
+            normalize_pkey(PKCS8_PEM),
+            (PKCS8_PEM.trim().to_string(), KeyFormat::Pem)
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn repairs_single_line_pem() {
+        let single_line = PKCS1_PEM.split_whitespace().collect::<Vec<_>>().join(" ");
+        let (pem, format) = normalize_pkey(&single_line);
+
+        assert_eq!(format, KeyFormat::SingleLinePem);
+        assert_eq!(pem, PKCS1_PEM);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn decodes_base64_encoded_pem() {
+        let (pem, format) = normalize_pkey(&Base64::encode_string(PKCS1_PEM.as_bytes()));
+
+        assert_eq!(format, KeyFormat::Base64Pem);
+        assert_eq!(pem, PKCS1_PEM.trim());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn decodes_base64_encoded_der() {
+        let pkcs1 = Pem::from_str(PKCS1_PEM).unwrap();
+        let pkcs8 = Pem::from_str(PKCS8_PEM).unwrap();
+
+        let (pem, format) = normalize_pkey(&Base64::encode_string(pkcs1.contents()));
+        assert_eq!(format, KeyFormat::Base64Pkcs1Der);
+        assert_eq!(pem, PKCS1_PEM);
+
+        let (pem, format) = normalize_pkey(&Base64::encode_string(pkcs8.contents()));
+        assert_eq!(format, KeyFormat::Base64Pkcs8Der);
+        assert_eq!(pem, PKCS8_PEM);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn leaves_unrecognized_keys_untouched() {
+        assert_eq!(
+            normalize_pkey("not a key"),
+            ("not a key".to_string(), KeyFormat::Unknown)
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn splits_concatenated_pem_blocks() {
+        let data = format!("{PKCS8_PEM}{PKCS1_PEM}");
+
+        assert_eq!(
+            split_pkeys(&data),
+            vec![PKCS8_PEM.trim_end(), PKCS1_PEM.trim_end()]
+        );
+        assert_eq!(split_pkeys(PKCS8_PEM), vec![PKCS8_PEM]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn fingerprint_matches_github_app_settings() {
+        // openssl rsa -in private-key.pem -pubout -outform DER | openssl sha256 -binary | openssl base64
+        let expected = "SHA256:sM15U7gPK4usLs1QLduaOTTCCxJaLPoREkm+kajCm2s=";
+
+        assert_eq!(
+            fingerprint_sha256(&load_pkey(PKCS8_PEM, None).unwrap()).unwrap(),
+            expected
+        );
+        assert_eq!(
+            fingerprint_sha256(&load_pkey(PKCS1_PEM, None).unwrap()).unwrap(),
+            expected
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn loads_rsa_jwk() {
+        assert_eq!(normalize_pkey(JWK).1, KeyFormat::Jwk);
+        assert_eq!(
+            load_pkey(JWK, None).unwrap(),
+            load_pkey(PKCS8_PEM, None).unwrap()
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn loads_rsa_jwk_without_primes() {
+        let mut jwk: serde_json::Value = serde_json::from_str(JWK).unwrap();
+        for member in ["p", "q", "dp", "dq", "qi"] {
+            jwk.as_object_mut().unwrap().remove(member);
+        }
+
+        assert_eq!(
+            load_pkey(&jwk.to_string(), None).unwrap(),
+            load_pkey(PKCS8_PEM, None).unwrap()
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_non_rsa_jwk() {
+        assert_eq!(
+            load_pkey(r#"{"kty":"EC","crv":"P-256","x":"","y":"","d":""}"#, None)
+                .unwrap_err()
+                .to_string(),
+            "unsupported JWK key type 'EC', GitHub Apps sign with RS256 and need an RSA key"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn reports_missing_jwk_members() {
+        assert_eq!(
+            load_pkey(r#"{"kty":"RSA","n":"AQAB","e":"AQAB"}"#, None)
+                .unwrap_err()
+                .to_string(),
+            "JWK is missing the 'd' member"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn decrypts_pbes2_encrypted_private_keys() {
+        let pkey = load_pkey(PKCS8_PEM, None).unwrap();
+
+        assert_eq!(load_pkey(ENCRYPTED_PEM, Some("passphrase")).unwrap(), pkey);
+        assert_eq!(
+            load_pkey(SCRYPT_ENCRYPTED_PEM, Some("passphrase")).unwrap(),
+            pkey
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn encrypted_private_key_requires_passphrase() {
+        assert_eq!(
+            load_pkey(ENCRYPTED_PEM, None).unwrap_err().to_string(),
+            "private-key-passphrase must be set to decrypt an encrypted private key"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_wrong_passphrase() {
+        assert!(load_pkey(ENCRYPTED_PEM, Some("wrong"))
+            .unwrap_err()
+            .to_string()
+            .starts_with("failed to decrypt private key, check private-key-passphrase"));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn accepts_github_app_keys() {
+        assert_eq!(check_pem(PKCS8_PEM), Ok(()));
+        assert_eq!(check_pem(PKCS1_PEM), Ok(()));
+        assert_eq!(
+            check_pkcs8(&load_pkey(PKCS1_PEM, None).unwrap()),
+            Ok(RsaKeyInfo {
+                bits: 2048,
+                exponent: 65537
+            })
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_stray_text_around_pem() {
+        assert_eq!(
+            check_pem(&format!("Bag Attributes\n{PKCS8_PEM}")),
+            Err(KeyProblem::StrayText)
+        );
+        assert_eq!(
+            check_pem(&format!("{PKCS8_PEM}garbage\n")),
+            Err(KeyProblem::StrayText)
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_multiple_pem_blocks() {
+        assert_eq!(
+            check_pem(&format!("{PKCS8_PEM}{CERTIFICATE_PEM}")),
+            Err(KeyProblem::MultipleBlocks(2))
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_public_keys_and_certificates() {
+        assert_eq!(check_pem(PUBLIC_KEY_PEM), Err(KeyProblem::PublicKey));
+        assert_eq!(check_pem(CERTIFICATE_PEM), Err(KeyProblem::Certificate));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_non_rsa_keys() {
+        assert_eq!(
+            check_pkcs8(&load_pkey(EC_PEM, None).unwrap()),
+            Err(KeyProblem::NotRsa("EC".to_string()))
+        );
+        assert_eq!(
+            check_pkcs8(&load_pkey(ED25519_PEM, None).unwrap()),
+            Err(KeyProblem::NotRsa("Ed25519".to_string()))
+        );
+        assert_eq!(
+            KeyProblem::NotRsa("DSA".to_string()).to_string(),
+            "uses algorithm DSA, not RSA"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_keys_under_2048_bits() {
+        let problem = check_pkcs8(&load_pkey(WEAK_PKCS1_PEM, None).unwrap()).unwrap_err();
+
+        assert_eq!(problem, KeyProblem::WeakModulus(1024));
+        assert_eq!(
+            problem.to_string(),
+            "has a 1024-bit modulus, at least 2048 bits are required"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_only_small_or_even_exponents() {
+        assert!(weak_exponent(3));
+        assert!(weak_exponent(65536));
+        assert!(!weak_exponent(17));
+        assert!(!weak_exponent(RsaKeyInfo::GITHUB_EXPONENT));
+        assert_eq!(
+            KeyProblem::WeakExponent(3).to_string(),
+            "has public exponent 3, which must be odd and greater than 3"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_malformed_pkcs8() {
+        assert_eq!(check_pkcs8(b"garbage"), Err(KeyProblem::Malformed));
+    }
+}
@@ -0,0 +1,151 @@
+use base64ct::{Base64, Base64Unpadded, Base64Url, Base64UrlUnpadded, Encoding};
+use wasm_actions::prelude::Error;
+
+/// Runs `command` through the shell with `input` on stdin and returns the RS256 signature it prints.
+pub fn sign_sha256(command: &str, input: &[u8]) -> Result<Vec<u8>, Error> {
+    let stdout = platform::exec(command, input)
+        .map_err(|e| Error::from(format!("signer-command failed: {e}")))?;
+    decode_signature(&stdout)
+}
+
+/// Accepts either a raw signature or its base64 (or base64url) encoding.
+fn decode_signature(stdout: &[u8]) -> Result<Vec<u8>, Error> {
+    if let Ok(text) = std::str::from_utf8(stdout) {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(Error::from("signer-command did not print a signature"));
+        }
+        if let Ok(signature) = Base64::decode_vec(text)
+            .or_else(|_| Base64Unpadded::decode_vec(text))
+            .or_else(|_| Base64Url::decode_vec(text))
+            .or_else(|_| Base64UrlUnpadded::decode_vec(text))
+        {
+            return Ok(signature);
+        }
+    }
+    Ok(stdout.to_vec())
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    use wasm_actions::prelude::Error;
+    use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue};
+    use web_sys::js_sys::{Array, Object, Reflect, Uint8Array};
+
+    #[wasm_bindgen(module = "node:child_process")]
+    extern "C" {
+        #[wasm_bindgen(catch, js_name = execSync)]
+        fn exec_sync(command: &str, options: &Object) -> Result<Uint8Array, JsValue>;
+    }
+
+    pub fn exec(command: &str, input: &[u8]) -> Result<Vec<u8>, Error> {
+        let options = Object::new();
+        Reflect::set(&options, &"input".into(), &Uint8Array::from(input)).map_err(Error::from)?;
+        // let the command report errors in the job log
+        let stdio = Array::of3(&"pipe".into(), &"pipe".into(), &"inherit".into());
+        Reflect::set(&options, &"stdio".into(), &stdio).map_err(Error::from)?;
+        let stdout = match exec_sync(command, &options) {
+            Ok(stdout) => stdout,
+            // a command that does not read stdin may exit before the input is written
+            Err(e) if closed_stdin(&e) => Reflect::get(&e, &"stdout".into())
+                .map_err(Error::from)?
+                .unchecked_into(),
+            Err(e) => return Err(Error::from(e)),
+        };
+        Ok(stdout.to_vec())
+    }
+
+    /// Whether `execSync` failed only because writing the input hit EPIPE, while the command succeeded.
+    fn closed_stdin(e: &JsValue) -> bool {
+        let get = |key: &str| Reflect::get(e, &key.into()).unwrap_or(JsValue::UNDEFINED);
+        get("code").as_string().as_deref() == Some("EPIPE") && get("status").as_f64() == Some(0.0)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use std::{
+        io::{ErrorKind, Write},
+        process::{Command, Stdio},
+    };
+    use wasm_actions::prelude::Error;
+
+    pub fn exec(command: &str, input: &[u8]) -> Result<Vec<u8>, Error> {
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        let mut child = Command::new(shell)
+            .args([flag, command])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(Error::new)?;
+        let written = child
+            .stdin
+            .take()
+            .ok_or_else(|| Error::from("failed to open stdin"))?
+            .write_all(input);
+        match written {
+            // a command that does not read stdin may exit before the input is written
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => {}
+            written => written.map_err(Error::new)?,
+        }
+
+        let output = child.wait_with_output().map_err(Error::new)?;
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(Error::from(format!("exited with {}", output.status)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn decodes_base64_signatures() {
+        assert_eq!(decode_signature(b"AQID\n").unwrap(), vec![1, 2, 3]);
+        assert_eq!(decode_signature(b"-_8").unwrap(), vec![0xfb, 0xff]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn keeps_raw_signatures() {
+        assert_eq!(
+            decode_signature(&[0x00, 0xff, 0x10]).unwrap(),
+            vec![0x00, 0xff, 0x10]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_empty_output() {
+        assert_eq!(
+            decode_signature(b"\n").unwrap_err().to_string(),
+            "signer-command did not print a signature"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn passes_signing_input_on_stdin() {
+        assert_eq!(
+            sign_sha256("base64", b"header.payload").unwrap(),
+            b"header.payload"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn tolerates_commands_that_ignore_stdin() {
+        // larger than a pipe buffer, so the write fails once the command has exited
+        let input = vec![b'.'; 1 << 20];
+
+        assert_eq!(
+            sign_sha256("printf signature", &input).unwrap(),
+            b"signature"
+        );
+    }
+}
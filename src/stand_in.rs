@@ -0,0 +1,136 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+use wasm_bindgen::{closure::Closure, prelude::wasm_bindgen, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::js_sys::{Object, Promise, Reflect};
+
+#[wasm_bindgen(module = "node:http")]
+extern "C" {
+    type Server;
+    type IncomingMessage;
+    type ServerResponse;
+
+    #[wasm_bindgen(js_name = createServer)]
+    fn create_server(handler: &Closure<dyn FnMut(IncomingMessage, ServerResponse)>) -> Server;
+
+    #[wasm_bindgen(method)]
+    fn listen(this: &Server, port: u16, host: &str, callback: &JsValue);
+
+    #[wasm_bindgen(method)]
+    fn address(this: &Server) -> Object;
+
+    #[wasm_bindgen(method)]
+    fn close(this: &Server);
+
+    #[wasm_bindgen(method, getter)]
+    fn method(this: &IncomingMessage) -> String;
+
+    #[wasm_bindgen(method, getter)]
+    fn url(this: &IncomingMessage) -> String;
+
+    #[wasm_bindgen(method, getter)]
+    fn headers(this: &IncomingMessage) -> Object;
+
+    #[wasm_bindgen(method, js_name = writeHead)]
+    fn write_head(this: &ServerResponse, status: u16, headers: &Object);
+
+    #[wasm_bindgen(method)]
+    fn end(this: &ServerResponse, body: &str);
+}
+
+/// A JSON response of the stand-in server.
+#[derive(Clone)]
+pub struct Reply {
+    status: u16,
+    body: &'static str,
+    headers: Vec<(&'static str, String)>,
+}
+
+impl Reply {
+    pub fn json(status: u16, body: &'static str) -> Self {
+        Self {
+            status,
+            body,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+}
+
+/// A request received by the stand-in server.
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    headers: Object,
+}
+
+impl Request {
+    /// The value of the header `name`, which must be lowercase, or an empty string.
+    pub fn header(&self, name: &str) -> String {
+        Reflect::get(&self.headers, &name.into())
+            .ok()
+            .and_then(|value| value.as_string())
+            .unwrap_or_default()
+    }
+}
+
+/// A stand-in HTTP server that answers requests with `replies` in turn, repeating the last one,
+/// and records every request.
+pub struct StandInServer {
+    server: Server,
+    pub address: String,
+    pub requests: Rc<RefCell<Vec<Request>>>,
+    _handler: Closure<dyn FnMut(IncomingMessage, ServerResponse)>,
+}
+
+impl StandInServer {
+    pub async fn start(replies: Vec<Reply>) -> Self {
+        let mut replies = VecDeque::from(replies);
+        let requests = Rc::new(RefCell::new(Vec::new()));
+        let recorded = requests.clone();
+        let handler = Closure::new(move |req: IncomingMessage, res: ServerResponse| {
+            recorded.borrow_mut().push(Request {
+                method: req.method(),
+                url: req.url(),
+                headers: req.headers(),
+            });
+            let reply = if replies.len() > 1 {
+                replies.pop_front().unwrap()
+            } else {
+                replies.front().cloned().unwrap()
+            };
+            let headers = Object::new();
+            Reflect::set(&headers, &"Content-Type".into(), &"application/json".into()).unwrap();
+            for (name, value) in &reply.headers {
+                Reflect::set(&headers, &(*name).into(), &value.into()).unwrap();
+            }
+            res.write_head(reply.status, &headers);
+            res.end(reply.body);
+        });
+        let server = create_server(&handler);
+        let listening = Promise::new(&mut |resolve, _| {
+            server.listen(0, "127.0.0.1", &resolve);
+        });
+        JsFuture::from(listening).await.unwrap();
+        let port = Reflect::get(&server.address(), &"port".into())
+            .unwrap()
+            .as_f64()
+            .unwrap();
+
+        Self {
+            server,
+            address: format!("http://127.0.0.1:{port}"),
+            requests,
+            _handler: handler,
+        }
+    }
+}
+
+impl Drop for StandInServer {
+    fn drop(&mut self) {
+        self.server.close();
+    }
+}
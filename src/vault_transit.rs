@@ -0,0 +1,177 @@
+use base64ct::{Base64, Encoding};
+use serde::{Deserialize, Serialize};
+use wasm_actions::prelude::Error;
+
+/// Signs with the `sign` endpoint of a HashiCorp Vault Transit secrets engine.
+#[derive(Clone)]
+pub struct VaultTransit {
+    url: String,
+    token: String,
+    client: reqwest::Client,
+}
+
+#[derive(Serialize)]
+struct SignRequest {
+    input: String,
+    signature_algorithm: &'static str,
+}
+
+#[derive(Deserialize)]
+struct SignResponse {
+    data: SignResponseData,
+}
+
+#[derive(Deserialize)]
+struct SignResponseData {
+    signature: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    #[serde(default)]
+    errors: Vec<String>,
+}
+
+impl VaultTransit {
+    pub fn new(address: &str, mount: &str, key: &str, token: &str) -> Self {
+        Self {
+            url: format!(
+                "{}/v1/{}/sign/{}/sha2-256",
+                address.trim_end_matches('/'),
+                mount.trim_matches('/'),
+                key
+            ),
+            token: token.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Returns the RS256 signature of `input` made by the Transit key.
+    pub async fn sign_sha256(&self, input: &[u8]) -> Result<Vec<u8>, Error> {
+        let res = self
+            .client
+            .post(&self.url)
+            .header("X-Vault-Token", &self.token)
+            .json(&sign_request(input))
+            .send()
+            .await
+            .map_err(|e| Error::from(format!("Vault Transit sign request failed: {e}")))?;
+
+        let status = res.status();
+        if !status.is_success() {
+            let errors = res
+                .json::<ErrorResponse>()
+                .await
+                .map(|body| body.errors.join("; "))
+                .unwrap_or_default();
+            return Err(Error::from(format!(
+                "Vault Transit sign request failed with {status}: {errors}"
+            )));
+        }
+
+        let res: SignResponse = res.json().await.map_err(Error::new)?;
+        decode_signature(&res.data.signature)
+    }
+}
+
+fn sign_request(input: &[u8]) -> SignRequest {
+    SignRequest {
+        input: Base64::encode_string(input),
+        // Transit defaults to PSS, which RS256 does not use
+        signature_algorithm: "pkcs1v15",
+    }
+}
+
+/// Decodes a `vault:v<version>:<base64>` signature.
+fn decode_signature(signature: &str) -> Result<Vec<u8>, Error> {
+    let encoded = signature
+        .strip_prefix("vault:v")
+        .and_then(|rest| rest.split_once(':'))
+        .filter(|(version, _)| version.parse::<u32>().is_ok())
+        .map(|(_, encoded)| encoded)
+        .ok_or_else(|| Error::from(format!("unexpected Vault signature format: {signature}")))?;
+    Base64::decode_vec(encoded)
+        .map_err(|e| Error::from(format!("Vault signature is not base64: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn builds_pkcs1v15_sign_request() {
+        assert_eq!(
+            serde_json::to_value(sign_request(b"header.payload")).unwrap(),
+            serde_json::json!({
+                "input": "aGVhZGVyLnBheWxvYWQ=",
+                "signature_algorithm": "pkcs1v15",
+            })
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn decodes_vault_signatures() {
+        assert_eq!(decode_signature("vault:v1:AQID").unwrap(), vec![1, 2, 3]);
+        assert_eq!(decode_signature("vault:v12:AQID").unwrap(), vec![1, 2, 3]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_signatures_without_vault_prefix() {
+        assert_eq!(
+            decode_signature("AQID").unwrap_err().to_string(),
+            "unexpected Vault signature format: AQID"
+        );
+    }
+
+    /// Tests against a stand-in Vault, which needs Node's `http` module.
+    #[cfg(target_arch = "wasm32")]
+    mod stand_in {
+        use super::*;
+        use crate::stand_in::{Reply, StandInServer};
+
+        #[wasm_bindgen_test]
+        async fn signs_with_transit_key() {
+            let vault = StandInServer::start(vec![Reply::json(
+                200,
+                r#"{"data":{"signature":"vault:v1:AQID"}}"#,
+            )])
+            .await;
+            let transit = VaultTransit::new(
+                &format!("{}/", vault.address),
+                "/transit/",
+                "app",
+                "s.token",
+            );
+
+            assert_eq!(
+                transit.sign_sha256(b"header.payload").await.unwrap(),
+                vec![1, 2, 3]
+            );
+            let requests = vault.requests.borrow();
+            assert_eq!(requests.len(), 1);
+            assert_eq!(requests[0].method, "POST");
+            assert_eq!(requests[0].url, "/v1/transit/sign/app/sha2-256");
+            assert_eq!(requests[0].header("x-vault-token"), "s.token");
+        }
+
+        #[wasm_bindgen_test]
+        async fn reports_vault_errors() {
+            let vault = StandInServer::start(vec![Reply::json(
+                403,
+                r#"{"errors":["permission denied"]}"#,
+            )])
+            .await;
+            let transit = VaultTransit::new(&vault.address, "transit", "app", "s.token");
+
+            assert_eq!(
+                transit
+                    .sign_sha256(b"header.payload")
+                    .await
+                    .unwrap_err()
+                    .to_string(),
+                "Vault Transit sign request failed with 403 Forbidden: permission denied"
+            );
+        }
+    }
+}